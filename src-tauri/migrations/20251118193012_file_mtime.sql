@@ -0,0 +1,2 @@
+-- File modification time (unix ms), together with file_size used to skip unchanged files on rescan
+alter table addons add column file_mtime integer;
@@ -0,0 +1,105 @@
+-- Full text search over addons and workshop items.
+-- Search rowids match addons.rowid and workshop_items.publishedfileid, kept up to date by triggers below
+
+create view addons_search_source as
+select addons.rowid as search_id,
+       addons.title,
+       addons.author,
+       addons.tagline,
+       wi.description,
+       wi.tags as workshop_tags,
+       (select group_concat(tag, ' ') from addon_tags where addon_tags.hash = addons.file_hash) as user_tags
+from addons
+left join workshop_items wi on wi.publishedfileid = addons.workshop_id;
+
+create virtual table addons_search using fts5
+(
+    title,
+    author,
+    tagline,
+    description,
+    workshop_tags,
+    user_tags
+);
+
+create virtual table workshop_search using fts5
+(
+    title,
+    author,
+    description,
+    workshop_tags
+);
+
+insert into addons_search (rowid, title, author, tagline, description, workshop_tags, user_tags)
+select search_id, title, author, tagline, description, workshop_tags, user_tags from addons_search_source;
+
+insert into workshop_search (rowid, title, author, description, workshop_tags)
+select publishedfileid, title, creator_id, description, tags from workshop_items;
+
+-- addons
+create trigger addons_search_insert after insert on addons
+begin
+    insert into addons_search (rowid, title, author, tagline, description, workshop_tags, user_tags)
+    select search_id, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where search_id = new.rowid;
+end;
+
+create trigger addons_search_update after update of title, author, tagline, workshop_id on addons
+begin
+    delete from addons_search where rowid = old.rowid;
+    insert into addons_search (rowid, title, author, tagline, description, workshop_tags, user_tags)
+    select search_id, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where search_id = new.rowid;
+end;
+
+create trigger addons_search_delete after delete on addons
+begin
+    delete from addons_search where rowid = old.rowid;
+end;
+
+-- addon_tags
+create trigger addon_tags_search_insert after insert on addon_tags
+begin
+    delete from addons_search where rowid in (select rowid from addons where file_hash = new.hash);
+    insert into addons_search (rowid, title, author, tagline, description, workshop_tags, user_tags)
+    select search_id, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where search_id in (select rowid from addons where file_hash = new.hash);
+end;
+
+create trigger addon_tags_search_delete after delete on addon_tags
+begin
+    delete from addons_search where rowid in (select rowid from addons where file_hash = old.hash);
+    insert into addons_search (rowid, title, author, tagline, description, workshop_tags, user_tags)
+    select search_id, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where search_id in (select rowid from addons where file_hash = old.hash);
+end;
+
+-- workshop_items. INSERT OR REPLACE does not fire delete triggers, so insert clears any existing row first
+create trigger workshop_search_insert after insert on workshop_items
+begin
+    delete from workshop_search where rowid = new.publishedfileid;
+    insert into workshop_search (rowid, title, author, description, workshop_tags)
+    values (new.publishedfileid, new.title, new.creator_id, new.description, new.tags);
+
+    delete from addons_search where rowid in (select rowid from addons where workshop_id = new.publishedfileid);
+    insert into addons_search (rowid, title, author, tagline, description, workshop_tags, user_tags)
+    select search_id, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where search_id in (select rowid from addons where workshop_id = new.publishedfileid);
+end;
+
+create trigger workshop_search_update after update of title, creator_id, description, tags on workshop_items
+begin
+    delete from workshop_search where rowid = old.publishedfileid;
+    insert into workshop_search (rowid, title, author, description, workshop_tags)
+    values (new.publishedfileid, new.title, new.creator_id, new.description, new.tags);
+
+    delete from addons_search where rowid in (select rowid from addons where workshop_id = new.publishedfileid);
+    insert into addons_search (rowid, title, author, tagline, description, workshop_tags, user_tags)
+    select search_id, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where search_id in (select rowid from addons where workshop_id = new.publishedfileid);
+end;
+
+create trigger workshop_search_delete after delete on workshop_items
+begin
+    delete from workshop_search where rowid = old.publishedfileid;
+end;
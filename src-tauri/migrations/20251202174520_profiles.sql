@@ -0,0 +1,27 @@
+-- Named sets of enabled addons
+create table profiles
+(
+    id         integer primary key autoincrement,
+    name       text    not null unique,
+    created_at integer not null,
+    updated_at integer not null
+);
+
+-- Each item is either a managed addon (file_hash) or a workshop folder item (workshop_id).
+-- Not a foreign key to addons, so items of removed addons can still be reported as missing
+create table profile_items
+(
+    profile_id  integer not null,
+    file_hash   blob,
+    workshop_id integer,
+    check ((file_hash is null) != (workshop_id is null)),
+    foreign key (profile_id) references profiles (id) ON DELETE CASCADE
+);
+create unique index profile_items_hash on profile_items (profile_id, file_hash);
+create unique index profile_items_workshop on profile_items (profile_id, workshop_id);
+
+-- Keep items pointing at an addon when its file is updated in place
+create trigger profile_items_hash_update after update of file_hash on addons
+begin
+    update profile_items set file_hash = new.file_hash where file_hash = old.file_hash;
+end;
@@ -0,0 +1,12 @@
+-- Internal file list of each managed addon's vpk, used for finding addons that replace the same files.
+-- Paths are case insensitive, as they are in game
+create table addon_files
+(
+    hash      blob    not null,
+    path      text    not null collate nocase,
+    file_size integer not null,
+    crc       integer not null,
+    primary key (hash, path),
+    foreign key (hash) references addons (file_hash) ON UPDATE CASCADE ON DELETE CASCADE
+);
+create index addon_files_path on addon_files (path);
@@ -0,0 +1,19 @@
+-- Info parsed from the vpk of each item in the workshop folder, linked to workshop_items by publishedfileid.
+-- Not a foreign key, as the vpk can be scanned before the item is fetched from steam
+create table workshop_files
+(
+    publishedfileid integer not null,
+    file_hash       blob    not null,
+    file_size       integer not null,
+    file_mtime      integer,
+
+    -- extracted addon info
+    title       text,
+    author      text,
+    version     text,
+    tagline     text,
+    chapter_ids text, -- comma separated list of coop chapter ids, if set
+    flags       integer not null default 0, -- Bit field for AddonFlags
+
+    primary key (publishedfileid)
+);
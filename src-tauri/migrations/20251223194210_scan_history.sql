@@ -0,0 +1,26 @@
+-- Each scan session, id is the scan_id addons are marked with
+create table scans
+(
+    id             integer primary key,
+    started_at     integer not null,
+    ended_at       integer,
+    speed          text    not null,
+    status         text    not null, -- running, complete or aborted
+    total          integer not null default 0,
+    added          integer not null default 0,
+    updated        integer not null default 0,
+    skipped        integer not null default 0,
+    failed         integer not null default 0,
+    aborted_reason text
+);
+
+-- Files that could not be scanned or stored during a scan
+create table scan_errors
+(
+    scan_id     integer not null,
+    filename    text    not null,
+    workshop_id integer, -- set if file is in the workshop folder
+    error       text    not null,
+    foreign key (scan_id) references scans (id) ON DELETE CASCADE
+);
+create index scan_errors_scan on scan_errors (scan_id);
@@ -0,0 +1,9 @@
+-- Files a running scan has yet to process, so a scan interrupted by the app closing can be resumed
+create table scan_queue
+(
+    scan_id     integer not null,
+    path        text    not null,
+    workshop_id integer, -- set if file is in the workshop folder
+    primary key (scan_id, path),
+    foreign key (scan_id) references scans (id) ON DELETE CASCADE
+);
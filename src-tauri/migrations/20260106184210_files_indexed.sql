@@ -0,0 +1,4 @@
+-- Set once an addon's file list has been read, even if its vpk tree could not be read and it has no addon_files rows.
+-- Addons without it are rescanned, so they get indexed
+alter table addons add column files_indexed integer not null default 0;
+update addons set files_indexed = 1 where exists (select 1 from addon_files where addon_files.hash = addons.file_hash);
@@ -0,0 +1,2 @@
+-- Preview image of workshop items, used as the thumbnail of addons without an image of their own
+alter table workshop_items add column preview_url text null;
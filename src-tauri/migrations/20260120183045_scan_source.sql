@@ -0,0 +1,3 @@
+-- What stored a scan record: 'scan' for scans, or 'watcher', 'copy' or 'update' for records of the files that failed
+-- to be stored outside of a scan. Those are kept until the next complete scan
+alter table scans add column source text not null default 'scan';
@@ -0,0 +1,118 @@
+-- Rebuilds the full text search tables. addons has no integer primary key, so its rowid can change on VACUUM,
+-- addons_search rows are now matched to addons by file_hash. workshop_search no longer has the author column,
+-- workshop items only store the creator's numeric id
+
+drop trigger addons_search_insert;
+drop trigger addons_search_update;
+drop trigger addons_search_delete;
+drop trigger addon_tags_search_insert;
+drop trigger addon_tags_search_delete;
+drop trigger workshop_search_insert;
+drop trigger workshop_search_update;
+drop trigger workshop_search_delete;
+drop table addons_search;
+drop table workshop_search;
+drop view addons_search_source;
+
+create view addons_search_source as
+select addons.file_hash,
+       addons.title,
+       addons.author,
+       addons.tagline,
+       wi.description,
+       wi.tags as workshop_tags,
+       (select group_concat(tag, ' ') from addon_tags where addon_tags.hash = addons.file_hash) as user_tags
+from addons
+left join workshop_items wi on wi.publishedfileid = addons.workshop_id;
+
+create virtual table addons_search using fts5
+(
+    file_hash unindexed,
+    title,
+    author,
+    tagline,
+    description,
+    workshop_tags,
+    user_tags
+);
+
+create virtual table workshop_search using fts5
+(
+    title,
+    description,
+    workshop_tags
+);
+
+insert into addons_search (file_hash, title, author, tagline, description, workshop_tags, user_tags)
+select file_hash, title, author, tagline, description, workshop_tags, user_tags from addons_search_source;
+
+insert into workshop_search (rowid, title, description, workshop_tags)
+select publishedfileid, title, description, tags from workshop_items;
+
+-- addons
+create trigger addons_search_insert after insert on addons
+begin
+    insert into addons_search (file_hash, title, author, tagline, description, workshop_tags, user_tags)
+    select file_hash, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where file_hash = new.file_hash;
+end;
+
+create trigger addons_search_update after update of file_hash, title, author, tagline, workshop_id on addons
+begin
+    delete from addons_search where file_hash = old.file_hash;
+    insert into addons_search (file_hash, title, author, tagline, description, workshop_tags, user_tags)
+    select file_hash, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where file_hash = new.file_hash;
+end;
+
+create trigger addons_search_delete after delete on addons
+begin
+    delete from addons_search where file_hash = old.file_hash;
+end;
+
+-- addon_tags
+create trigger addon_tags_search_insert after insert on addon_tags
+begin
+    delete from addons_search where file_hash = new.hash;
+    insert into addons_search (file_hash, title, author, tagline, description, workshop_tags, user_tags)
+    select file_hash, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where file_hash = new.hash;
+end;
+
+create trigger addon_tags_search_delete after delete on addon_tags
+begin
+    delete from addons_search where file_hash = old.hash;
+    insert into addons_search (file_hash, title, author, tagline, description, workshop_tags, user_tags)
+    select file_hash, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where file_hash = old.hash;
+end;
+
+-- workshop_items. INSERT OR REPLACE does not fire delete triggers, so insert clears any existing row first
+create trigger workshop_search_insert after insert on workshop_items
+begin
+    delete from workshop_search where rowid = new.publishedfileid;
+    insert into workshop_search (rowid, title, description, workshop_tags)
+    values (new.publishedfileid, new.title, new.description, new.tags);
+
+    delete from addons_search where file_hash in (select file_hash from addons where workshop_id = new.publishedfileid);
+    insert into addons_search (file_hash, title, author, tagline, description, workshop_tags, user_tags)
+    select file_hash, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where file_hash in (select file_hash from addons where workshop_id = new.publishedfileid);
+end;
+
+create trigger workshop_search_update after update of title, description, tags on workshop_items
+begin
+    delete from workshop_search where rowid = old.publishedfileid;
+    insert into workshop_search (rowid, title, description, workshop_tags)
+    values (new.publishedfileid, new.title, new.description, new.tags);
+
+    delete from addons_search where file_hash in (select file_hash from addons where workshop_id = new.publishedfileid);
+    insert into addons_search (file_hash, title, author, tagline, description, workshop_tags, user_tags)
+    select file_hash, title, author, tagline, description, workshop_tags, user_tags
+    from addons_search_source where file_hash in (select file_hash from addons where workshop_id = new.publishedfileid);
+end;
+
+create trigger workshop_search_delete after delete on workshop_items
+begin
+    delete from workshop_search where rowid = old.publishedfileid;
+end;
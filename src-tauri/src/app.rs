@@ -0,0 +1,203 @@
+use crate::commands;
+use crate::commands::addons as cmd_addons;
+use crate::commands::config as cmd_config;
+use crate::commands::installations as cmd_installations;
+use crate::commands::logs as cmd_logs;
+use crate::commands::profiles as cmd_profiles;
+use crate::modules::cfg::{self, AppConfig};
+use crate::modules::export::FastDlSignal;
+use crate::modules::installations::{get_store_folder, DEFAULT_INSTALLATION_ID};
+use crate::modules::store::{AddonStorage, AddonStorageContainer, FileHash};
+use crate::modules::thumbnails::ThumbnailCache;
+use crate::scan::AddonScanner;
+use crate::scan::scheduler::start_scheduler;
+use crate::scan::watcher::AddonWatcher;
+use log::{LevelFilter, debug, info, warn};
+use std::str::FromStr;
+use std::sync::Arc;
+use tauri::async_runtime::{Mutex, RwLock};
+use tauri::{Manager, RunEvent};
+use tauri_plugin_log::Target;
+use tauri_plugin_log::TargetKind;
+
+fn log_level() -> LevelFilter {
+    let level = LevelFilter::from_str(option_env!("APP_LOG_LEVEL").unwrap_or("trace"))
+        .expect("invalid log level");
+    println!("log level: {}", level);
+    level
+}
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let app = tauri::Builder::default()
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(
+            tauri_plugin_log::Builder::new()
+                .level(tauri_plugin_log::log::LevelFilter::Info)
+                .max_file_size(1_048_576) // 1 MB
+                .rotation_strategy(tauri_plugin_log::RotationStrategy::KeepSome(4))
+                .build(),
+        )
+        .plugin(
+            tauri_plugin_log::Builder::new()
+                // Set default level to INFO, but our crate TRACE
+                .level(log::LevelFilter::Info)
+                .level_for("l4d2_addon_manager_lib", log_level())
+                // in addition to defaults, also send to frontend
+                .target(Target::new(TargetKind::Webview))
+                .build(),
+        )
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_store::Builder::default().build())
+        .plugin(tauri_plugin_opener::init())
+        // Serves addon thumbnails by id (hash or workshop id), as thumbnail://localhost/{id}
+        .register_asynchronous_uri_scheme_protocol("thumbnail", |ctx, request, responder| {
+            let thumbnails = ctx.app_handle().state::<ThumbnailCache>().inner().clone();
+            let addons = ctx.app_handle().state::<AddonStorageContainer>().inner().clone();
+            let id = request.uri().path().trim_start_matches('/').to_string();
+            tauri::async_runtime::spawn(async move {
+                // Workshop items are keyed by the hash of their vpk
+                let hash = match id.parse::<i64>() {
+                    Ok(workshop_id) => addons.read().await.get_workshop_file_hash(workshop_id).await.ok().flatten(),
+                    Err(_) => FileHash::from_str(&id).ok(),
+                };
+                let thumbnail = match hash {
+                    Some(hash) => match thumbnails.get(&hash) {
+                        Some(image) => Some(image),
+                        // Addons without an image of their own use their workshop item's preview
+                        None => {
+                            let url = addons.read().await.get_preview_url(&hash).await.ok().flatten();
+                            match url {
+                                Some(url) => thumbnails
+                                    .fetch_preview(&hash, &url)
+                                    .await
+                                    .inspect_err(|e| warn!("thumbnail for {}: {}", hash, e))
+                                    .ok(),
+                                None => None,
+                            }
+                        }
+                    },
+                    None => None,
+                };
+                let response = match thumbnail {
+                    Some(image) => tauri::http::Response::builder()
+                        .header("Content-Type", "image/jpeg")
+                        .header("Cache-Control", "max-age=3600")
+                        .body(image),
+                    None => tauri::http::Response::builder().status(404).body(Vec::new()),
+                };
+                responder.respond(response.unwrap());
+            });
+        })
+        .setup(|app| {
+            log_panics::init();
+            info!("starting {}/v{} (os={}) (debug={})", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), std::env::consts::OS, cfg!(debug_assertions));
+            app.manage(cfg::StaticData::new(app));
+            let data_dir = app.path().app_local_data_dir().unwrap();
+
+            app.manage(FastDlSignal::default());
+
+            let config = AppConfig::load(data_dir.join("config.json"));
+            let addons_folder = config.addons_folder.clone();
+            // Database and thumbnails are of the active installation
+            let store_folder = get_store_folder(&data_dir, config.active_installation.unwrap_or(DEFAULT_INSTALLATION_ID));
+            app.manage(Mutex::new(config));
+            app.manage(ThumbnailCache::new(&store_folder));
+            let db = tauri::async_runtime::block_on(async move {
+                let db = AddonStorage::new(store_folder)
+                    .await
+                    .expect("failed to create db");
+                db.run_migrations().await.expect("migrations failed");
+                let db = Arc::new(RwLock::new(db));
+                db
+            });
+            app.manage(db.clone());
+
+            let thumbnails = app.state::<ThumbnailCache>().inner().clone();
+            let scanner =
+                tokio::sync::Mutex::new(AddonScanner::new(db.clone(), Arc::new(app.handle().clone()), thumbnails));
+            app.manage(scanner);
+
+            let mut watcher = AddonWatcher::new(db.clone(), app.handle().clone());
+            if let Some(addons_folder) = addons_folder {
+                if let Err(e) = watcher.start(addons_folder) {
+                    warn!("could not start watcher: {}", e);
+                }
+            }
+            app.manage(tokio::sync::Mutex::new(watcher));
+
+            start_scheduler(app.handle().clone());
+
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            commands::init,
+            commands::export,
+            commands::export_fastdl,
+            commands::export_fastdl_cancel,
+            commands::import,
+            commands::clear_database,
+            cmd_logs::get_logs,
+            cmd_logs::open_logs_folder,
+            cmd_logs::upload_logs,
+            cmd_config::choose_game_folder,
+            cmd_config::set_game_folder,
+            cmd_config::set_config,
+            cmd_config::server_generate_config,
+            cmd_installations::installations_list,
+            cmd_installations::installations_add,
+            cmd_installations::installations_remove,
+            cmd_installations::installations_select,
+            cmd_installations::installations_copy_addons,
+            cmd_installations::installations_copy_profile,
+            cmd_addons::addons_counts,
+            cmd_addons::addons_list_managed,
+            cmd_addons::addons_list_workshop,
+            cmd_addons::addons_start_scan,
+            cmd_addons::addons_abort_scan,
+            cmd_addons::addons_pause_scan,
+            cmd_addons::addons_resume_scan,
+            cmd_addons::addons_unfinished_scan,
+            cmd_addons::addons_resume_unfinished_scan,
+            cmd_addons::addons_scan_history,
+            cmd_addons::addons_scan_errors,
+            cmd_addons::addons_broken,
+            cmd_addons::addons_migrate,
+            cmd_addons::addons_unsubscribe,
+            cmd_addons::addons_set_state,
+            cmd_addons::addons_delete,
+            cmd_addons::addons_tag_add,
+            cmd_addons::addons_tag_del,
+            cmd_addons::addons_duplicates,
+            cmd_addons::addons_resolve_duplicates,
+            cmd_addons::addons_conflicts,
+            cmd_addons::addons_vpk_tree,
+            cmd_addons::addons_vpk_extract,
+            cmd_addons::addons_refresh_workshop,
+            cmd_addons::addons_check_updates,
+            cmd_addons::addons_update,
+            cmd_addons::addons_workshop_subscriptions,
+            cmd_profiles::profiles_list,
+            cmd_profiles::profiles_create,
+            cmd_profiles::profiles_update,
+            cmd_profiles::profiles_duplicate,
+            cmd_profiles::profiles_delete,
+            cmd_profiles::profiles_apply,
+            cmd_profiles::profiles_import_collection,
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application");
+    app.run(|app, event| match event {
+        RunEvent::ExitRequested { .. } => {
+            let db = app.state::<AddonStorageContainer>().inner().clone();
+            // let db = db.blocking_lock();
+            tauri::async_runtime::spawn(async move {
+                let db = db.read().await;
+                debug!("cleaning up db...");
+                db.close().await;
+                debug!("cleaning up db... done");
+            });
+        }
+        _ => {}
+    })
+}
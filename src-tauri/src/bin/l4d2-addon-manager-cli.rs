@@ -0,0 +1,4 @@
+// Headless version of the app, for managing addons over ssh on servers without a desktop
+fn main() {
+    l4d2_addon_manager_lib::cli::run()
+}
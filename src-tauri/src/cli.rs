@@ -0,0 +1,322 @@
+use crate::modules::addons::ItemResult;
+use crate::modules::addons::set_addons_state;
+use crate::modules::cfg::AppConfig;
+use crate::modules::export::{FastDlSignal, FastDlSource, export_app, export_fastdl, get_fastdl_addons};
+use crate::modules::installations::{get_store_folder, DEFAULT_INSTALLATION_ID};
+use crate::modules::migrate::migrate_workshop;
+use crate::modules::store::{AddonFilter, AddonStorage, FileHash};
+use crate::modules::thumbnails::ThumbnailCache;
+use crate::scan::{AddonScanner, ScanSpeed};
+use crate::util::events::EventSink;
+use clap::{Parser, Subcommand, ValueEnum};
+use l4d2_addon_parser::addon_list::AddonList;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::util::runtime::block_on;
+use tokio::sync::RwLock;
+
+/// Same as the desktop app's app_local_data_dir, so both use the same database
+const APP_IDENTIFIER: &str = "me.jackz.l4d2-addon-manager";
+
+#[derive(Parser)]
+#[command(name = "l4d2-addon-manager-cli", version, about = "Manage L4D2 addons without the desktop app")]
+struct Cli {
+    /// Folder of the database and config, defaults to the desktop app's
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+    /// Addons folder to use instead of the configured one
+    #[arg(long, global = true, conflicts_with = "installation")]
+    addons_folder: Option<PathBuf>,
+    /// Id of installation to use instead of the active one
+    #[arg(long, global = true)]
+    installation: Option<u32>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Scans the addons folder, adding new and changed addons. Continues an unfinished scan if there is one
+    Scan {
+        /// Defaults to the configured scan speed
+        #[arg(long, value_enum)]
+        speed: Option<Speed>,
+        /// Number of worker threads, overrides speed
+        #[arg(long)]
+        threads: Option<u8>,
+        /// Limit how fast files are read while hashing, overrides speed
+        #[arg(long)]
+        max_mb_per_sec: Option<u32>,
+        /// Start a new scan, even if there is an unfinished one
+        #[arg(long)]
+        restart: bool,
+        /// Start a new scan of every file, including unchanged ones, and print files/sec and
+        /// time spent hashing, parsing and storing
+        #[arg(long)]
+        benchmark: bool,
+    },
+    /// Lists game and server installations
+    Installations,
+    /// Lists managed addons
+    List {
+        /// Full text search over title, author, tagline, workshop info and tags
+        #[arg(long)]
+        filter: Option<String>,
+        /// Only enabled addons
+        #[arg(long, conflicts_with = "disabled")]
+        enabled: bool,
+        /// Only disabled addons
+        #[arg(long)]
+        disabled: bool,
+        /// Print as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Enables addons in addonlist.txt, by filename
+    Enable { filenames: Vec<String> },
+    /// Disables addons in addonlist.txt, by filename
+    Disable { filenames: Vec<String> },
+    /// Adds or removes a tag of an addon
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// Exports the database and config to a zip file
+    Export {
+        path: PathBuf,
+        /// Include the addons folder
+        #[arg(long)]
+        with_addons: bool,
+    },
+    /// Copies workshop items into the addons folder, unsubscribing if a steam api key is set
+    Migrate { ids: Vec<i64> },
+    /// Writes a fastdl tree (.bz2 copies of maps and custom assets) of a profile's addons
+    Fastdl {
+        dest: PathBuf,
+        #[arg(long)]
+        profile: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagAction {
+    Add { filename: String, tag: String },
+    Del { filename: String, tag: String },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum Speed {
+    Maximum,
+    Normal,
+    Background,
+}
+impl From<Speed> for ScanSpeed {
+    fn from(speed: Speed) -> Self {
+        match speed {
+            Speed::Maximum => ScanSpeed::Maximum,
+            Speed::Normal => ScanSpeed::Normal,
+            Speed::Background => ScanSpeed::Background,
+        }
+    }
+}
+
+/// Prints progress and state events to the terminal
+struct TerminalEvents;
+impl EventSink for TerminalEvents {
+    fn emit_json(&self, event: &str, payload: serde_json::Value) {
+        match event {
+            "scan_progress" | "export_progress" | "fastdl_progress" => {
+                eprint!("\r{} / {}", payload["value"], payload["total"]);
+                std::io::stderr().flush().ok();
+            }
+            "scan_state" => match payload["state"].as_str() {
+                Some("started") => eprintln!("Scan started"),
+                Some("paused") => eprintln!("\nScan paused"),
+                Some("resumed") => eprintln!("Scan resumed"),
+                Some("aborted") => eprintln!("\nScan aborted: {}", payload["reason"]),
+                Some("complete") => eprintln!(
+                    "\nScan complete in {}s: {} scanned, {} added, {} updated, {} skipped, {} failed",
+                    payload["time"], payload["total"], payload["added"], payload["updated"], payload["skipped"], payload["failed"]
+                ),
+                _ => {}
+            },
+            "scan_benchmark" => {
+                eprintln!(
+                    "Benchmark: {} files in {}ms, {:.1} files/sec",
+                    payload["files"], payload["elapsed_ms"], payload["files_per_sec"].as_f64().unwrap_or(0.0)
+                );
+                eprintln!(
+                    "  hashing {}ms, parsing {}ms (summed over threads), storing {}ms",
+                    payload["hash_ms"], payload["parse_ms"], payload["db_ms"]
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+fn print_results(results: &[ItemResult]) -> Result<(), String> {
+    let mut errors = 0;
+    for result in results {
+        match result {
+            ItemResult::Ok { filename } => println!("OK    {}", filename),
+            ItemResult::Error { filename, error } => {
+                errors += 1;
+                println!("ERROR {}: {}", filename, error);
+            }
+        }
+    }
+    match errors {
+        0 => Ok(()),
+        _ => Err(format!("{} / {} failed", errors, results.len())),
+    }
+}
+
+async fn find_hash(addons: &AddonStorage, filename: &str) -> Result<FileHash, String> {
+    addons
+        .list_hash_filenames()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|(_, name)| name.as_deref() == Some(filename))
+        .map(|(hash, _)| hash)
+        .ok_or_else(|| format!("no addon with filename {}", filename))
+}
+
+async fn run_command(cli: Cli) -> Result<(), String> {
+    let data_dir = match cli.data_dir {
+        Some(dir) => dir,
+        None => dirs::data_local_dir()
+            .ok_or("could not find data dir, set --data-dir".to_string())?
+            .join(APP_IDENTIFIER),
+    };
+    let mut config = AppConfig::load(data_dir.join("config.json"));
+    if cli.addons_folder.is_some() {
+        config.addons_folder = cli.addons_folder;
+    }
+    if let Some(id) = cli.installation {
+        config.set_active_installation(id)?;
+    }
+    let addons_folder = config
+        .addons_folder
+        .clone()
+        .ok_or("no addons folder configured, set --addons-folder".to_string())?;
+    let addonlist_path = addons_folder.parent().unwrap().join("addonlist.txt");
+
+    let store_folder = get_store_folder(&data_dir, config.active_installation.unwrap_or(DEFAULT_INSTALLATION_ID));
+    let addons = AddonStorage::new(store_folder.clone()).await?;
+    addons.run_migrations().await.map_err(|e| e.to_string())?;
+
+    let result = match cli.command {
+        Command::Scan { speed, threads, max_mb_per_sec, restart, benchmark } => {
+            let speed = speed.map(ScanSpeed::from).unwrap_or(config.scan_speed);
+            let speed = match (threads, max_mb_per_sec) {
+                (None, None) => speed,
+                (threads, max_mb_per_sec) => ScanSpeed::Custom {
+                    threads: threads.unwrap_or(speed.threads()),
+                    max_mb_per_sec,
+                },
+            };
+            let unfinished = match restart || benchmark {
+                true => None,
+                false => addons.get_unfinished_scan().await.map_err(|e| e.to_string())?,
+            };
+            let addons = Arc::new(RwLock::new(addons));
+            let mut scanner = AddonScanner::new(addons.clone(), Arc::new(TerminalEvents), ThumbnailCache::new(&store_folder));
+            match unfinished {
+                Some(scan) => {
+                    eprintln!("Resuming unfinished scan {}", scan.id);
+                    scanner.resume_unfinished(scan.id as u32, addons_folder, speed);
+                }
+                None if benchmark => {
+                    scanner.benchmark(addons_folder, speed);
+                }
+                None => {
+                    scanner.start(addons_folder, speed);
+                }
+            }
+            scanner.wait().await;
+            addons.read().await.close().await;
+            return Ok(());
+        }
+        Command::List { filter, enabled, disabled, json } => {
+            let addon_list = AddonList::new(&addonlist_path).ok();
+            let filter = AddonFilter {
+                query: filter,
+                enabled: (enabled || disabled).then_some(enabled),
+                ..Default::default()
+            };
+            let page = addons.list(addon_list, None, filter).await.map_err(|e| e.to_string())?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&page).map_err(|e| e.to_string())?);
+            } else {
+                for entry in &page.entries {
+                    let state = match entry.enabled {
+                        Some(true) => "on ",
+                        Some(false) => "off",
+                        None => "-  ",
+                    };
+                    let filename = entry.info.filename.as_deref().unwrap_or("(missing)");
+                    println!("{} {:<40} {} [{}]", state, filename, entry.info.title, entry.tags.join(", "));
+                }
+                println!("{} addons", page.total);
+            }
+            Ok(())
+        }
+        Command::Installations => {
+            for install in &config.installations {
+                let active = if config.active_installation == Some(install.id) { "*" } else { " " };
+                let kind = if install.server_mode { "server" } else { "game" };
+                println!("{} {:<3} {:<30} {:<6} {}", active, install.id, install.name, kind, install.addons_folder.display());
+            }
+            Ok(())
+        }
+        Command::Enable { filenames } => print_results(&set_addons_state(&addonlist_path, filenames, true)?),
+        Command::Disable { filenames } => print_results(&set_addons_state(&addonlist_path, filenames, false)?),
+        Command::Tag { action } => match action {
+            TagAction::Add { filename, tag } => {
+                let hash = find_hash(&addons, &filename).await?;
+                addons.add_tag(hash, tag).await.map_err(|e| e.to_string())
+            }
+            TagAction::Del { filename, tag } => {
+                let hash = find_hash(&addons, &filename).await?;
+                addons.del_tag(hash, tag).await.map_err(|e| e.to_string())
+            }
+        },
+        Command::Export { path, with_addons } => {
+            let addons_folder = with_addons.then_some(addons_folder);
+            let version = env!("CARGO_PKG_VERSION").to_string();
+            export_app(&TerminalEvents, path, data_dir, store_folder, version, addons_folder)
+                .map(|path| eprintln!("\nExported to {}", path.display()))
+        }
+        Command::Migrate { ids } => {
+            let results = tokio::task::spawn_blocking(move || migrate_workshop(&config, ids))
+                .await
+                .map_err(|e| e.to_string())??;
+            print_results(&results)
+        }
+        Command::Fastdl { dest, profile } => {
+            let paths = get_fastdl_addons(&addons, &addons_folder, FastDlSource::Profile { id: profile }).await?;
+            let summary = tokio::task::spawn_blocking(move || export_fastdl(&TerminalEvents, &FastDlSignal::default(), paths, dest))
+                .await
+                .map_err(|e| e.to_string())??;
+            eprintln!(
+                "\n{} files ({} written, {} unchanged), {} conflicting files left out",
+                summary.files, summary.written, summary.unchanged, summary.conflicts.len()
+            );
+            Ok(())
+        }
+    };
+    addons.close().await;
+    result
+}
+
+/// Entry point of the cli binary, for managing addons without a webview
+pub fn run() {
+    let cli = Cli::parse();
+    if let Err(e) = block_on(run_command(cli)) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
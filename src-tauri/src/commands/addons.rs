@@ -1,19 +1,28 @@
 use std::ops::Deref;
+use crate::models::scan::{ScanError, ScanRecord};
+use crate::modules::addons::{set_addons_state, ItemResult};
 use crate::modules::cfg::AppConfigContainer;
-use crate::modules::store::{AddonEntry, AddonStorageContainer, FileHash, SelectedSort};
+use crate::modules::conflicts::{find_conflicts, AddonConflict};
+use crate::modules::duplicates::{find_duplicates, resolve_duplicates, AddonFileRef, DuplicateGroup};
+use crate::modules::sort::SelectedSort;
+use crate::modules::store::{AddonFilter, AddonPage, AddonStorageContainer, FileHash, ListError};
+use crate::modules::thumbnails::ThumbnailCache;
+use crate::modules::updates::{check_updates, update_addons, HttpSource, OutdatedAddon};
+use crate::modules::vpk::{VpkDirectory, VpkTreeNode};
+use crate::scan::acf::{read_workshop_subscriptions, WorkshopSubscription};
+use crate::scan::worker::scan_workshop_thread;
 use crate::scan::{ScanSpeed, ScannerContainer};
 use crate::util::get_addon_list;
-use l4d2_addon_parser::addon_list::AddonList;
 use log::{debug, error, info, trace};
-use serde::{Deserialize, Serialize};
 use sqlx::__rt::spawn_blocking;
+use std::path::PathBuf;
 use std::time::Duration;
-use tauri::{AppHandle, State};
+use tauri::State;
 use crate::modules::migrate::{migrate_workshop, unsubscribe_workshop};
 
 #[tauri::command]
 pub async fn addons_counts(addons: State<'_, AddonStorageContainer>) -> Result<(u32, u32), String> {
-    let addons = addons.lock().await;
+    let addons = addons.read().await;
     addons.counts().await.map_err(|e| e.to_string())
 }
 
@@ -22,25 +31,26 @@ pub async fn addons_counts(addons: State<'_, AddonStorageContainer>) -> Result<(
 pub async fn addons_list_managed(
     addons: State<'_, AddonStorageContainer>,
     cfg: State<'_, AppConfigContainer>,
-    sort: Option<SelectedSort>
-) -> Result<Vec<AddonEntry>, String> {
+    sort: Option<SelectedSort>,
+    filter: Option<AddonFilter>,
+) -> Result<AddonPage, ListError> {
     let addon_list = get_addon_list(cfg).await;
-    let addons = addons.lock().await;
-    addons.list(addon_list, sort).await.map_err(|e| e.to_string())
+    let addons = addons.read().await;
+    addons.list(addon_list, sort, filter.unwrap_or_default()).await
 }
 
 #[tauri::command]
 pub async fn addons_list_workshop(
     addons: State<'_, AddonStorageContainer>,
     cfg: State<'_, AppConfigContainer>,
-    sort: Option<SelectedSort>
-) -> Result<Vec<AddonEntry>, String> {
+    sort: Option<SelectedSort>,
+    filter: Option<AddonFilter>,
+) -> Result<AddonPage, ListError> {
     let addon_list = get_addon_list(cfg).await;
-    let addons = addons.lock().await;
+    let addons = addons.read().await;
     addons
-        .list_workshop(addon_list, sort)
+        .list_workshop(addon_list, sort, filter.unwrap_or_default())
         .await
-        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -49,14 +59,16 @@ pub async fn addons_start_scan(
     scanner: State<'_, ScannerContainer>,
     speed: Option<ScanSpeed>,
 ) -> Result<(), String> {
-    let addons_folder = {
+    // Scans use the configured speed, unless given one
+    let (addons_folder, speed) = {
         let cfg = cfg.lock().await;
-        cfg.addons_folder
+        let addons_folder = cfg.addons_folder
             .clone()
-            .ok_or_else(|| "no addon folder configured".to_string())?
+            .ok_or_else(|| "no addon folder configured".to_string())?;
+        (addons_folder, speed.unwrap_or(cfg.scan_speed))
     };
     let mut scanner = scanner.lock().await;
-    match scanner.start(addons_folder, speed.unwrap_or_default()) {
+    match scanner.start(addons_folder, speed) {
         true => Ok(()),
         false => Err("A scan is already in progress".to_string()),
     }
@@ -72,36 +84,127 @@ pub async fn addons_abort_scan(
     Ok(())
 }
 
-#[derive(Serialize, Clone)]
-#[serde(tag = "result")]
-#[serde(rename_all = "lowercase")]
-pub enum ItemResult {
-    Ok { filename: String },
-    Error { filename: String, error: String },
+#[tauri::command]
+/// Parks the running scan's workers, keeping its queue
+pub async fn addons_pause_scan(scanner: State<'_, ScannerContainer>) -> Result<(), String> {
+    let mut scanner = scanner.lock().await;
+    match scanner.pause() {
+        true => Ok(()),
+        false => Err("No running scan to pause".to_string()),
+    }
 }
-impl ItemResult {
-    pub fn ok(filename: String) -> Self {
-        ItemResult::Ok { filename }
+
+#[tauri::command]
+pub async fn addons_resume_scan(scanner: State<'_, ScannerContainer>) -> Result<(), String> {
+    let mut scanner = scanner.lock().await;
+    match scanner.resume() {
+        true => Ok(()),
+        false => Err("No paused scan to resume".to_string()),
     }
-    pub fn error(filename: String, error: String) -> Self {
-        ItemResult::Error { filename, error }
+}
+
+#[tauri::command]
+/// Returns a scan that never ended with files left to scan, such as one interrupted by the app closing
+pub async fn addons_unfinished_scan(
+    addons: State<'_, AddonStorageContainer>,
+    scanner: State<'_, ScannerContainer>,
+) -> Result<Option<ScanRecord>, String> {
+    if scanner.lock().await.check_running() {
+        return Ok(None);
+    }
+    let addons = addons.read().await;
+    addons.get_unfinished_scan().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+/// Continues the unfinished scan from its remaining files, instead of starting over
+pub async fn addons_resume_unfinished_scan(
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+    scanner: State<'_, ScannerContainer>,
+    speed: Option<ScanSpeed>,
+) -> Result<(), String> {
+    let (addons_folder, speed) = {
+        let cfg = cfg.lock().await;
+        let addons_folder = cfg.addons_folder
+            .clone()
+            .ok_or_else(|| "no addon folder configured".to_string())?;
+        (addons_folder, speed.unwrap_or(cfg.scan_speed))
+    };
+    let scan = {
+        let addons = addons.read().await;
+        addons.get_unfinished_scan().await.map_err(|e| e.to_string())?
+    };
+    let scan = scan.ok_or_else(|| "No unfinished scan to resume".to_string())?;
+    let mut scanner = scanner.lock().await;
+    match scanner.resume_unfinished(scan.id as u32, addons_folder, speed) {
+        true => Ok(()),
+        false => Err("A scan is already in progress".to_string()),
     }
 }
 
+#[tauri::command]
+/// Returns past scans, newest first
+pub async fn addons_scan_history(
+    addons: State<'_, AddonStorageContainer>,
+    limit: Option<u32>,
+) -> Result<Vec<ScanRecord>, String> {
+    let addons = addons.read().await;
+    addons.list_scans(limit.unwrap_or(20)).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+/// Returns files that failed during a scan, with the reason
+pub async fn addons_scan_errors(
+    addons: State<'_, AddonStorageContainer>,
+    id: i64,
+) -> Result<Vec<ScanError>, String> {
+    let addons = addons.read().await;
+    addons.list_scan_errors(id).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+/// Returns files in the addons folder that the latest scan could not add, such as corrupt vpks
+pub async fn addons_broken(
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+) -> Result<Vec<ScanError>, String> {
+    let addons_folder = {
+        let cfg = cfg.lock().await;
+        cfg.addons_folder
+            .clone()
+            .ok_or_else(|| "addons folder missing".to_string())?
+    };
+    let broken = {
+        let addons = addons.read().await;
+        addons.list_broken_files().await.map_err(|e| e.to_string())?
+    };
+    // Skip files removed since
+    Ok(broken
+        .into_iter()
+        .filter(|file| match file.workshop_id {
+            Some(_) => addons_folder.join("workshop").join(&file.filename).exists(),
+            None => addons_folder.join(&file.filename).exists(),
+        })
+        .collect())
+}
+
 #[tauri::command]
 pub async fn addons_migrate(
-    app: AppHandle,
+    cfg: State<'_, AppConfigContainer>,
     ids: Vec<i64>,
 ) -> Result<Vec<ItemResult>, String> {
-    spawn_blocking(move || migrate_workshop(app, ids)).await
+    let cfg = cfg.lock().await.clone();
+    spawn_blocking(move || migrate_workshop(&cfg, ids)).await
 }
 
 #[tauri::command]
 pub async fn addons_unsubscribe(
-    app: AppHandle,
+    cfg: State<'_, AppConfigContainer>,
     ids: Vec<i64>,
 ) -> Result<Vec<ItemResult>, String> {
-    spawn_blocking(move || unsubscribe_workshop(app, ids)).await
+    let cfg = cfg.lock().await.clone();
+    spawn_blocking(move || unsubscribe_workshop(&cfg, ids)).await
 }
 
 #[tauri::command]
@@ -121,20 +224,7 @@ pub async fn addons_set_state(
             .join("addonlist.txt")
     };
     // TODO: test disabling it via addonlist.txt (if it gets overwritten, works). if not then .disabled suffix
-    let mut list =
-        AddonList::new(&addonslist_path).map_err(|e| format!("failed to check state: {}", e))?;
-    let results = filenames
-        .into_iter()
-        .map(
-            |filename| match list.set_enabled(filename.to_string(), state) {
-                Ok(()) => ItemResult::ok(filename),
-                Err(err) => ItemResult::error(filename, err.to_string()),
-            },
-        )
-        .collect();
-    list.save()
-        .map_err(|e| format!("failed to save addonlist.txt: {}", e))?;
-    Ok(results)
+    set_addons_state(&addonslist_path, filenames, state)
 }
 
 #[tauri::command]
@@ -169,7 +259,7 @@ pub async fn addons_delete(
             _ => None,
         })
         .collect();
-    let addons = addons.lock().await;
+    let addons = addons.read().await;
     addons
         .delete_filenames(deleted_filenames)
         .await
@@ -184,7 +274,7 @@ pub async fn addons_tag_add(
     tag: String
 ) -> Result<(), String> {
     let hash = FileHash::from_str(&id).map_err(|e| format!("bad id: {}", e))?;
-    let addons = addons.lock().await;
+    let addons = addons.read().await;
     addons.add_tag(hash, tag).await.map_err(|e| e.to_string())
 }
 
@@ -195,6 +285,184 @@ pub async fn addons_tag_del(
     tag: String
 ) -> Result<(), String> {
     let hash = FileHash::from_str(&id).map_err(|e| format!("bad id: {}", e))?;
-    let addons = addons.lock().await;
+    let addons = addons.read().await;
     addons.del_tag(hash, tag).await.map_err(|e| e.to_string())
-}
\ No newline at end of file
+}
+#[tauri::command]
+pub async fn addons_duplicates(
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+) -> Result<Vec<DuplicateGroup>, String> {
+    let addons_folder = {
+        let cfg = cfg.lock().await;
+        cfg.addons_folder
+            .clone()
+            .ok_or("addons folder missing".to_string())?
+    };
+    let managed = {
+        let addons = addons.read().await;
+        addons.list_filenames_workshop_ids().await.map_err(|e| e.to_string())?
+    };
+    Ok(spawn_blocking(move || find_duplicates(&addons_folder, managed)).await)
+}
+
+#[tauri::command]
+/// Keeps one file of a duplicate group, moving the others to trash
+pub async fn addons_resolve_duplicates(
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+    keep: AddonFileRef,
+    remove: Vec<AddonFileRef>,
+) -> Result<Vec<ItemResult>, String> {
+    let addons_folder = {
+        let cfg = cfg.lock().await;
+        cfg.addons_folder
+            .clone()
+            .ok_or("addons folder missing".to_string())?
+    };
+    resolve_duplicates(addons.inner().clone(), &addons_folder, keep, remove).await
+}
+
+#[tauri::command]
+/// Lists files that more than one enabled addon replaces, and which addon wins
+pub async fn addons_conflicts(
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+) -> Result<Vec<AddonConflict>, String> {
+    let addons_folder = {
+        let cfg = cfg.lock().await;
+        cfg.addons_folder
+            .clone()
+            .ok_or("addons folder missing".to_string())?
+    };
+    let addons = addons.read().await;
+    find_conflicts(&addons, &addons_folder).await
+}
+
+/// Opens the vpk of a managed addon by its id
+async fn open_addon_vpk(
+    cfg: &State<'_, AppConfigContainer>,
+    addons: &State<'_, AddonStorageContainer>,
+    id: &str,
+) -> Result<VpkDirectory, String> {
+    let addons_folder = {
+        let cfg = cfg.lock().await;
+        cfg.addons_folder
+            .clone()
+            .ok_or("addons folder missing".to_string())?
+    };
+    let hash = FileHash::from_str(id).map_err(|e| format!("bad id: {}", e))?;
+    let filename = {
+        let addons = addons.read().await;
+        addons
+            .get_filename(&hash)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "addon does not exist or its file is missing".to_string())?
+    };
+    let path = addons_folder.join(filename);
+    spawn_blocking(move || VpkDirectory::open(&path)).await
+}
+
+#[tauri::command]
+/// Returns the directory tree of files inside an addon
+pub async fn addons_vpk_tree(
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+    id: String,
+) -> Result<Vec<VpkTreeNode>, String> {
+    let vpk = open_addon_vpk(&cfg, &addons, &id).await?;
+    Ok(vpk.tree())
+}
+
+#[tauri::command]
+/// Extracts a file or directory inside an addon to dest folder, an empty path extracts everything.
+/// Returns number of files extracted
+pub async fn addons_vpk_extract(
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+    id: String,
+    path: String,
+    dest: PathBuf,
+) -> Result<u32, String> {
+    if !dest.is_dir() {
+        return Err(format!("{} is not a folder", dest.display()));
+    }
+    let vpk = open_addon_vpk(&cfg, &addons, &id).await?;
+    let extracted = spawn_blocking(move || vpk.extract(&path, &dest)).await?;
+    info!("Extracted {} files from addon {}", extracted, id);
+    Ok(extracted)
+}
+
+#[tauri::command]
+/// Fetches workshop info for managed addons linked to a workshop item, or only the given ids if set.
+/// Returns number of workshop items refreshed
+pub async fn addons_refresh_workshop(
+    addons: State<'_, AddonStorageContainer>,
+    ids: Option<Vec<i64>>,
+) -> Result<u32, String> {
+    let ids = match ids {
+        Some(ids) => ids,
+        None => {
+            let addons = addons.read().await;
+            addons.list_managed_workshop_ids().await.map_err(|e| e.to_string())?
+        }
+    };
+    debug!("refreshing {} workshop items", ids.len());
+    let items = spawn_blocking(move || scan_workshop_thread(ids)).await;
+    let count = items.len() as u32;
+    let addons = addons.read().await;
+    addons.add_workshop_items(items).await.map_err(|e| e.to_string())?;
+    info!("Refreshed {} workshop items", count);
+    Ok(count)
+}
+
+#[tauri::command]
+/// Lists managed addons with a newer version on the workshop than their file
+pub async fn addons_check_updates(
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+) -> Result<Vec<OutdatedAddon>, String> {
+    let addons_folder = {
+        let cfg = cfg.lock().await;
+        cfg.addons_folder
+            .clone()
+            .ok_or("addons folder missing".to_string())?
+    };
+    check_updates(addons.inner().clone(), &addons_folder).await
+}
+
+#[tauri::command]
+/// Downloads the latest workshop version of the given managed addons (by id), replacing their files
+pub async fn addons_update(
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+    thumbnails: State<'_, ThumbnailCache>,
+    ids: Vec<String>,
+) -> Result<Vec<ItemResult>, String> {
+    let addons_folder = {
+        let cfg = cfg.lock().await;
+        cfg.addons_folder
+            .clone()
+            .ok_or("addons folder missing".to_string())?
+    };
+    Ok(update_addons(addons.inner().clone(), &addons_folder, &thumbnails, &HttpSource::new(), ids).await)
+}
+
+#[tauri::command]
+/// Lists workshop subscriptions as Steam recorded them, including ones not downloaded yet. Works offline
+pub async fn addons_workshop_subscriptions(
+    cfg: State<'_, AppConfigContainer>,
+) -> Result<Vec<WorkshopSubscription>, String> {
+    let addons_folder = {
+        let cfg = cfg.lock().await;
+        // Dedicated servers have no subscriptions
+        if cfg.server_mode {
+            return Ok(Vec::new());
+        }
+        cfg.addons_folder
+            .clone()
+            .ok_or("addons folder missing".to_string())?
+    };
+    spawn_blocking(move || read_workshop_subscriptions(&addons_folder)).await
+}
@@ -1,11 +1,17 @@
 use crate::modules::cfg::{AppConfig, AppConfigContainer};
+use crate::modules::server::{generate_server_config, is_server_install, ServerConfigOutput};
+use crate::modules::store::AddonStorageContainer;
+use crate::scan::watcher::WatcherContainer;
 use crate::scan::{ScanSpeed, ScannerContainer};
-use log::{debug, info};
+use log::{debug, info, warn};
 use std::env::home_dir;
 use std::path::PathBuf;
 use tauri::{AppHandle, Emitter, State};
 use tauri_plugin_dialog::DialogExt;
 
+/// Executables of the game and of a dedicated server, which are both next to the left4dead2 folder
+const GAME_EXECUTABLES: [&str; 5] = ["left4dead2.exe", "left4dead2", "srcds.exe", "srcds_run", "srcds_linux"];
+
 #[tauri::command]
 pub async fn choose_game_folder(app: tauri::AppHandle) -> Result<PathBuf, String> {
     debug!("opening dialog");
@@ -29,9 +35,8 @@ pub async fn choose_game_folder(app: tauri::AppHandle) -> Result<PathBuf, String
                     .join("steam")
                     .join("steamapps")
                     .join("common"),
-            )
-            .add_filter("left4dead2.exe", &["exe"])
-            .set_file_name("left4dead2.exe");
+            );
+        // Linux executables have no extension, which a filter can not match
     }
     let path = dialog
         .blocking_pick_file()
@@ -39,8 +44,8 @@ pub async fn choose_game_folder(app: tauri::AppHandle) -> Result<PathBuf, String
         .into_path()
         .map_err(|e| e.to_string())?;
     let file_name = path.file_name().ok_or("invalid file".to_string())?;
-    if file_name != "left4dead2.exe" && file_name != "left4dead2" {
-        return Err(String::from("File must be a left4dead2 game executable"));
+    if !GAME_EXECUTABLES.iter().any(|exe| file_name == *exe) {
+        return Err(String::from("File must be a left4dead2 game or dedicated server (srcds) executable"));
     }
     Ok(path.parent().unwrap().join("left4dead2").join("addons"))
 }
@@ -51,17 +56,27 @@ pub async fn set_game_folder(
     cfg: State<'_, AppConfigContainer>,
     path: String,
     scanner: State<'_, ScannerContainer>,
+    watcher: State<'_, WatcherContainer>,
 ) -> Result<(), String> {
     debug!("setting addons folder to {}", path);
     let mut cfg = cfg.lock().await;
     let is_first_time = cfg.addons_folder.is_none();
     let path = PathBuf::from(path);
+    // Another folder can be a different kind of install
+    if cfg.addons_folder.as_ref() != Some(&path) {
+        cfg.server_mode = is_server_install(&path);
+        debug!("server_mode={}", cfg.server_mode);
+    }
     cfg.addons_folder = Some(path.clone());
+    cfg.sync_active_installation();
     // Start a scan at full speed if this is the first time
     if is_first_time {
         info!("First time setup, starting maximum scan");
         let mut scanner = scanner.lock().await;
-        scanner.start(path, ScanSpeed::Maximum);
+        scanner.start(path.clone(), ScanSpeed::Maximum);
+    }
+    if let Err(e) = watcher.lock().await.start(path) {
+        warn!("could not start watcher: {}", e);
     }
     cfg.save();
     Ok(())
@@ -70,13 +85,47 @@ pub async fn set_game_folder(
 pub async fn set_config(
     app: AppHandle,
     cfg: State<'_, AppConfigContainer>,
+    watcher: State<'_, WatcherContainer>,
     config: AppConfig,
 ) -> Result<(), String> {
     let mut cfg = cfg.lock().await;
     info!("set_config old {:?}", cfg);
     info!("set_config new {:?}", config);
-    cfg.replace(config.clone())?;
+    let folder_changed = cfg.addons_folder != config.addons_folder;
+    cfg.replace(config)?;
+    if folder_changed {
+        if let Some(folder) = &cfg.addons_folder {
+            cfg.server_mode = is_server_install(folder);
+            debug!("server_mode={}", cfg.server_mode);
+        }
+    }
     cfg.save();
-    app.emit("config_changed", config).ok();
+    if folder_changed {
+        let mut watcher = watcher.lock().await;
+        match &cfg.addons_folder {
+            Some(folder) => {
+                if let Err(e) = watcher.start(folder.clone()) {
+                    warn!("could not start watcher: {}", e);
+                }
+            }
+            None => watcher.stop(),
+        }
+    }
+    // Server mode may have been detected from the new folder
+    app.emit("config_changed", (*cfg).clone()).ok();
     Ok(())
 }
+
+#[tauri::command]
+/// Writes the server's workshop collection and download cvars to a config, for server mode
+pub async fn server_generate_config(
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+) -> Result<ServerConfigOutput, String> {
+    let cfg = cfg.lock().await.clone();
+    if !cfg.server_mode {
+        return Err("Server config can only be generated in server mode".to_string());
+    }
+    let addons = addons.read().await;
+    generate_server_config(&addons, &cfg).await
+}
@@ -0,0 +1,202 @@
+use crate::modules::addons::ItemResult;
+use crate::modules::cfg::{AppConfig, AppConfigContainer, Installation};
+use crate::modules::installations::{
+    add_installation, copy_addons, copy_profile, get_store_folder, open_installation_storage, remove_installation,
+    switch_installation, ProfileCopy,
+};
+use crate::modules::store::AddonStorageContainer;
+use crate::modules::thumbnails::ThumbnailCache;
+use crate::scan::watcher::WatcherContainer;
+use crate::scan::ScannerContainer;
+use log::warn;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::async_runtime::RwLock;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+#[derive(Serialize)]
+pub struct InstallationList {
+    installations: Vec<Installation>,
+    active: Option<u32>,
+}
+
+/// An installation's database and addons folder. Database is the app's shared one if installation is active
+struct InstallationStorage {
+    addons: AddonStorageContainer,
+    addons_folder: PathBuf,
+    thumbnails: ThumbnailCache,
+    opened: bool,
+}
+impl InstallationStorage {
+    async fn get(
+        app: &AppHandle,
+        cfg: &AppConfig,
+        addons: &AddonStorageContainer,
+        thumbnails: &ThumbnailCache,
+        id: u32,
+    ) -> Result<Self, String> {
+        let install = cfg
+            .get_installation(id)
+            .ok_or_else(|| format!("installation {} does not exist", id))?;
+        if cfg.active_installation == Some(id) {
+            return Ok(InstallationStorage {
+                addons: addons.clone(),
+                addons_folder: install.addons_folder.clone(),
+                thumbnails: thumbnails.clone(),
+                opened: false,
+            });
+        }
+        let data_dir = app.path().app_local_data_dir().expect("could not find data dir");
+        let storage = open_installation_storage(&data_dir, id).await?;
+        Ok(InstallationStorage {
+            addons: Arc::new(RwLock::new(storage)),
+            addons_folder: install.addons_folder.clone(),
+            thumbnails: ThumbnailCache::new(&get_store_folder(&data_dir, id)),
+            opened: true,
+        })
+    }
+
+    /// Closes database if it was opened for this
+    async fn close(self) {
+        if self.opened {
+            self.addons.read().await.close().await;
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn installations_list(cfg: State<'_, AppConfigContainer>) -> Result<InstallationList, String> {
+    let cfg = cfg.lock().await;
+    Ok(InstallationList {
+        installations: cfg.installations.clone(),
+        active: cfg.active_installation,
+    })
+}
+
+#[tauri::command]
+pub async fn installations_add(
+    app: AppHandle,
+    cfg: State<'_, AppConfigContainer>,
+    name: String,
+    addons_folder: PathBuf,
+) -> Result<Installation, String> {
+    let mut cfg = cfg.lock().await;
+    let install = add_installation(&mut cfg, &name, addons_folder)?;
+    cfg.save();
+    app.emit("config_changed", cfg.clone()).ok();
+    Ok(install)
+}
+
+#[tauri::command]
+pub async fn installations_remove(app: AppHandle, cfg: State<'_, AppConfigContainer>, id: u32) -> Result<(), String> {
+    let data_dir = app.path().app_local_data_dir().expect("could not find data dir");
+    let mut cfg = cfg.lock().await;
+    remove_installation(&mut cfg, &data_dir, id)?;
+    cfg.save();
+    app.emit("config_changed", cfg.clone()).ok();
+    Ok(())
+}
+
+#[tauri::command]
+/// Makes installation active, so scans, addon lists, addon states and migrations are of it
+pub async fn installations_select(
+    app: AppHandle,
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+    thumbnails: State<'_, ThumbnailCache>,
+    scanner: State<'_, ScannerContainer>,
+    watcher: State<'_, WatcherContainer>,
+    id: u32,
+) -> Result<(), String> {
+    let mut cfg = cfg.lock().await;
+    if cfg.active_installation == Some(id) {
+        return Ok(());
+    }
+    scanner.lock().await.abort(Some("installation changed".to_string())).await;
+    let mut watcher = watcher.lock().await;
+    watcher.stop();
+    let data_dir = app.path().app_local_data_dir().expect("could not find data dir");
+    switch_installation(&mut cfg, &data_dir, &addons, &thumbnails, id).await?;
+    cfg.save();
+    if let Some(folder) = &cfg.addons_folder {
+        if let Err(e) = watcher.start(folder.clone()) {
+            warn!("could not start watcher: {}", e);
+        }
+    }
+    app.emit("config_changed", cfg.clone()).ok();
+    Ok(())
+}
+
+#[tauri::command]
+/// Copies managed addons, by id (hash), from one installation's addons folder to another's
+pub async fn installations_copy_addons(
+    app: AppHandle,
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+    thumbnails: State<'_, ThumbnailCache>,
+    from: u32,
+    to: u32,
+    ids: Vec<String>,
+) -> Result<Vec<ItemResult>, String> {
+    if from == to {
+        return Err("Installations must be different".to_string());
+    }
+    let cfg = cfg.lock().await.clone();
+    let source = InstallationStorage::get(&app, &cfg, &addons, &thumbnails, from).await?;
+    let target = match InstallationStorage::get(&app, &cfg, &addons, &thumbnails, to).await {
+        Ok(target) => target,
+        Err(e) => {
+            source.close().await;
+            return Err(e);
+        }
+    };
+    let results = {
+        let from_addons = source.addons.read().await;
+        copy_addons(
+            &from_addons,
+            &source.addons_folder,
+            target.addons.clone(),
+            &target.addons_folder,
+            &target.thumbnails,
+            ids,
+        )
+        .await
+    };
+    source.close().await;
+    target.close().await;
+    Ok(results)
+}
+
+#[tauri::command]
+/// Copies a profile from one installation to another, addons it references are not copied
+pub async fn installations_copy_profile(
+    app: AppHandle,
+    cfg: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+    thumbnails: State<'_, ThumbnailCache>,
+    from: u32,
+    to: u32,
+    profile_id: i64,
+) -> Result<ProfileCopy, String> {
+    if from == to {
+        return Err("Installations must be different".to_string());
+    }
+    let cfg = cfg.lock().await.clone();
+    let source = InstallationStorage::get(&app, &cfg, &addons, &thumbnails, from).await?;
+    let target = match InstallationStorage::get(&app, &cfg, &addons, &thumbnails, to).await {
+        Ok(target) => target,
+        Err(e) => {
+            source.close().await;
+            return Err(e);
+        }
+    };
+    let result = {
+        let from_addons = source.addons.read().await;
+        let to_addons = target.addons.read().await;
+        copy_profile(&from_addons, &to_addons, profile_id).await
+    };
+    source.close().await;
+    target.close().await;
+    result
+}
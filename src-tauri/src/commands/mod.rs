@@ -1,9 +1,17 @@
 use crate::modules::cfg::AppConfigContainer;
-use crate::modules::cfg::{AppConfig, StaticData};
-use crate::modules::export::export_app;
-use crate::modules::store::AddonStorageContainer;
+use crate::modules::cfg::{AppConfig, ConfigImportMode, StaticData};
+use crate::modules::export::{
+    export_app, export_fastdl as export_fastdl_tree, get_fastdl_addons, prompt_fastdl_location,
+    prompt_save_location, FastDlSignal, FastDlSource, FastDlSummary,
+};
+use crate::modules::import::{
+    ImportSummary, extract_addons, extract_db, import_config, prompt_import_location,
+    read_import_summary,
+};
+use crate::modules::installations::{get_store_folder, DEFAULT_INSTALLATION_ID};
+use crate::modules::store::{AddonStorage, AddonStorageContainer};
 use crate::util::SetRoute;
-use log::debug;
+use log::{debug, info};
 use serde::Serialize;
 use std::fs::File;
 use std::io::BufRead;
@@ -14,7 +22,12 @@ use tauri_plugin_opener::OpenerExt;
 
 pub mod addons;
 pub mod config;
+pub mod installations;
 pub mod logs;
+pub mod profiles;
+
+/// Steam app id of the L4D2 dedicated server
+const DEDICATED_SERVER_APP_ID: u32 = 222860;
 
 #[derive(Serialize)]
 pub struct InitData {
@@ -30,14 +43,26 @@ pub async fn init(
     data: State<'_, StaticData>,
 ) -> Result<InitData, String> {
     let suggestion = {
-        match steamlocate::SteamDir::locate().and_then(|steam_dir| steam_dir.find_app(550)) {
+        // Game, or a dedicated server if only that is installed
+        let steam_app = steamlocate::SteamDir::locate().and_then(|steam_dir| match steam_dir.find_app(550)? {
+            Some(game) => Ok(Some(game)),
+            None => steam_dir.find_app(DEDICATED_SERVER_APP_ID),
+        });
+        match steam_app {
             Ok(Some((app, libr))) => { Some(libr.resolve_app_dir(&app).join("left4dead2/addons")) }
             _ => {
                 match std::env::consts::OS {
                     "windows" => Some(PathBuf::from(r"C:\Program Files (x86)\Steam\steamapps\common\Left 4 Dead2\left4dead2\addons")),
                     "linux" => {
                         app.path().home_dir()
-                            .map(|home| home.join(".steam/steam/steamapps/common/Left 4 Dead 2/left4dead2/addons"))
+                            .map(|home| {
+                                // steamcmd installs servers to ~/Steam
+                                let server = home.join("Steam/steamapps/common/Left 4 Dead 2 Dedicated Server/left4dead2/addons");
+                                match server.is_dir() {
+                                    true => server,
+                                    false => home.join(".steam/steam/steamapps/common/Left 4 Dead 2/left4dead2/addons"),
+                                }
+                            })
                             .ok()
                     },
                     _ => None
@@ -77,15 +102,18 @@ pub async fn export(
     with_addons: bool,
 ) -> Result<PathBuf, String> {
     let app_version = data.app_version.clone();
-    let addons_folder = if with_addons {
+    let data_dir = app.path().app_local_data_dir().expect("could not find data dir");
+    let (addons_folder, store_folder) = {
         let cfg = config.lock().await;
-        cfg.addons_folder.as_ref().map(|p| p.clone())
-    } else {
-        None
+        let store_folder = get_store_folder(&data_dir, cfg.active_installation.unwrap_or(DEFAULT_INSTALLATION_ID));
+        (cfg.addons_folder.clone().filter(|_| with_addons), store_folder)
     };
     let export_path = {
         let app = app.clone();
-        tokio::task::spawn_blocking(move || export_app(app, app_version, addons_folder))
+        tokio::task::spawn_blocking(move || {
+            let save_path = prompt_save_location(&app)?;
+            export_app(&app, save_path, data_dir, store_folder, app_version, addons_folder)
+        })
             .await
             .unwrap()?
     };
@@ -94,12 +122,139 @@ pub async fn export(
         .unwrap();
     Ok(export_path)
 }
+/// Writes a fastdl tree of the maps and custom assets of source into dest, or a folder the user picks.
+/// Progress is sent as fastdl_progress
+#[tauri::command]
+pub async fn export_fastdl(
+    app: AppHandle,
+    config: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+    signal: State<'_, FastDlSignal>,
+    source: FastDlSource,
+    dest: Option<PathBuf>,
+) -> Result<FastDlSummary, String> {
+    let addons_folder = {
+        let cfg = config.lock().await;
+        cfg.addons_folder.clone().ok_or("addons folder missing".to_string())?
+    };
+    let dest = match dest {
+        Some(dest) => dest,
+        None => {
+            let app = app.clone();
+            tokio::task::spawn_blocking(move || prompt_fastdl_location(&app))
+                .await
+                .unwrap()?
+        }
+    };
+    let paths = {
+        let addons = addons.read().await;
+        get_fastdl_addons(&addons, &addons_folder, source).await?
+    };
+    let signal = signal.inner().clone();
+    if !signal.start() {
+        return Err("A fastdl export is already in progress".to_string());
+    }
+    let result = {
+        let signal = signal.clone();
+        tokio::task::spawn_blocking(move || export_fastdl_tree(&app, &signal, paths, dest))
+            .await
+            .unwrap()
+    };
+    signal.finish();
+    result
+}
+
+#[tauri::command]
+pub async fn export_fastdl_cancel(signal: State<'_, FastDlSignal>) -> Result<(), String> {
+    signal.cancel();
+    Ok(())
+}
+
+/// Imports an export made by export. With dry_run, only the summary of the export is returned.
+/// Otherwise app is restarted once the import is complete
+#[tauri::command]
+pub async fn import(
+    app: AppHandle,
+    data: State<'_, StaticData>,
+    config: State<'_, AppConfigContainer>,
+    addons: State<'_, AddonStorageContainer>,
+    path: Option<PathBuf>,
+    dry_run: bool,
+    config_mode: Option<ConfigImportMode>,
+    with_addons: bool,
+) -> Result<ImportSummary, String> {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let app = app.clone();
+            tokio::task::spawn_blocking(move || prompt_import_location(&app))
+                .await
+                .unwrap()?
+        }
+    };
+    let summary = {
+        let app_version = data.app_version.clone();
+        let addons_folder = config.lock().await.addons_folder.clone();
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || read_import_summary(path, &app_version, addons_folder.as_ref()))
+            .await
+            .unwrap()?
+    };
+    if dry_run {
+        return Ok(summary);
+    }
+    info!("Starting import of {:?} (version={})", path, summary.version);
+
+    // Check the database migrates fine before anything is changed
+    let import_db_path = app
+        .path()
+        .app_local_data_dir()
+        .expect("could not find data dir")
+        .join("addon-manager.import.db");
+    {
+        let path = path.clone();
+        let dest = import_db_path.clone();
+        tokio::task::spawn_blocking(move || extract_db(&path, &dest))
+            .await
+            .unwrap()?;
+    }
+    let import_db = AddonStorage::open(import_db_path.clone()).await?;
+    let migrated = import_db.run_migrations().await;
+    import_db.close().await;
+    if let Err(e) = migrated {
+        std::fs::remove_file(&import_db_path).ok();
+        return Err(format!("imported database could not be migrated: {}", e));
+    }
+
+    let addons_folder = {
+        let mut cfg = config.lock().await;
+        import_config(&path, &mut cfg, config_mode.unwrap_or_default())?;
+        cfg.save();
+        cfg.addons_folder.clone()
+    };
+    if with_addons {
+        let addons_folder = addons_folder.ok_or_else(|| "no addons folder configured".to_string())?;
+        let app = app.clone();
+        tokio::task::spawn_blocking(move || extract_addons(&app, &path, &addons_folder))
+            .await
+            .unwrap()?;
+    }
+
+    info!("Import complete, replacing database and restarting");
+    let mut addons = addons.write().await;
+    addons
+        .danger_replace_database(&import_db_path)
+        .await
+        .map_err(|e| e.to_string())?;
+    app.restart();
+}
+
 #[tauri::command]
 pub async fn clear_database(
     addons: State<'_, AddonStorageContainer>,
     app: AppHandle,
 ) -> Result<(), String> {
-    let addons = addons.lock().await;
+    let mut addons = addons.write().await;
     addons
         .danger_drop_database()
         .await
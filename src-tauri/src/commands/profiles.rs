@@ -0,0 +1,118 @@
+use crate::modules::addons::ItemResult;
+use crate::models::profile::{ProfileEntry, ProfileItems};
+use crate::modules::cfg::AppConfigContainer;
+use crate::modules::collections::{import_collection, CollectionImport, SteamApi};
+use crate::modules::profiles::{apply_profile, get_enabled_items};
+use crate::modules::store::{AddonStorageContainer, FileHash};
+use std::path::PathBuf;
+use tauri::State;
+
+async fn get_addons_folder(cfg: &State<'_, AppConfigContainer>) -> Result<PathBuf, String> {
+    let cfg = cfg.lock().await;
+    cfg.addons_folder
+        .clone()
+        .ok_or_else(|| "addons folder missing".to_string())
+}
+
+fn parse_hashes(items: &ProfileItems) -> Result<Vec<FileHash>, String> {
+    items
+        .ids
+        .iter()
+        .map(|id| FileHash::from_str(id).map_err(|e| format!("bad id {}: {}", id, e)))
+        .collect()
+}
+
+#[tauri::command]
+pub async fn profiles_list(addons: State<'_, AddonStorageContainer>) -> Result<Vec<ProfileEntry>, String> {
+    let addons = addons.read().await;
+    let profiles = addons.list_profiles().await.map_err(|e| e.to_string())?;
+    let mut entries = Vec::with_capacity(profiles.len());
+    for profile in profiles {
+        let items = addons.get_profile_items(profile.id).await.map_err(|e| e.to_string())?;
+        entries.push(ProfileEntry { profile, items });
+    }
+    Ok(entries)
+}
+
+#[tauri::command]
+/// Creates a profile with the given items, or with the currently enabled addons if none given
+pub async fn profiles_create(
+    addons: State<'_, AddonStorageContainer>,
+    cfg: State<'_, AppConfigContainer>,
+    name: String,
+    items: Option<ProfileItems>,
+) -> Result<i64, String> {
+    let addons = addons.read().await;
+    let items = match items {
+        Some(items) => items,
+        None => get_enabled_items(&addons, &get_addons_folder(&cfg).await?).await?,
+    };
+    let hashes = parse_hashes(&items)?;
+    addons
+        .create_profile(&name, &hashes, &items.workshop_ids)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn profiles_update(
+    addons: State<'_, AddonStorageContainer>,
+    id: i64,
+    name: Option<String>,
+    items: Option<ProfileItems>,
+) -> Result<(), String> {
+    let hashes = items.as_ref().map(parse_hashes).transpose()?;
+    let addons = addons.read().await;
+    let new_items = hashes
+        .as_deref()
+        .zip(items.as_ref().map(|items| items.workshop_ids.as_slice()));
+    match addons.update_profile(id, name.as_deref(), new_items).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(format!("profile {} does not exist", id)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn profiles_duplicate(
+    addons: State<'_, AddonStorageContainer>,
+    id: i64,
+    name: String,
+) -> Result<i64, String> {
+    let addons = addons.read().await;
+    addons.duplicate_profile(id, &name).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn profiles_delete(addons: State<'_, AddonStorageContainer>, id: i64) -> Result<(), String> {
+    let addons = addons.read().await;
+    match addons.delete_profile(id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(format!("profile {} does not exist", id)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn profiles_apply(
+    addons: State<'_, AddonStorageContainer>,
+    cfg: State<'_, AppConfigContainer>,
+    id: i64,
+) -> Result<Vec<ItemResult>, String> {
+    let addons_folder = get_addons_folder(&cfg).await?;
+    let addons = addons.read().await;
+    apply_profile(&addons, &addons_folder, id).await
+}
+
+#[tauri::command]
+/// Creates a profile from a workshop collection url or id, named after the collection if name is not given
+pub async fn profiles_import_collection(
+    addons: State<'_, AddonStorageContainer>,
+    cfg: State<'_, AppConfigContainer>,
+    collection: String,
+    name: Option<String>,
+) -> Result<CollectionImport, String> {
+    let addons_folder = get_addons_folder(&cfg).await?;
+    let addons = addons.read().await;
+    import_collection(&SteamApi::new(), &addons, &addons_folder, &collection, name).await
+}
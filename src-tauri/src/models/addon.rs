@@ -13,7 +13,7 @@ pub struct StandardAddonWithTags {
     pub tags: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct WorkshopEntry {
     pub publishedfileid: u32,
     pub title: String,
@@ -25,3 +25,16 @@ pub struct WorkshopEntry {
     pub creator_id: String,
     pub tags: String,
 }
+
+#[derive(Debug, FromRow)]
+/// Workshop item, with the info parsed from its vpk if it has been scanned
+pub struct WorkshopEntryWithFile {
+    #[sqlx(flatten)]
+    pub entry: WorkshopEntry,
+
+    pub file_flags: Option<u32>,
+    pub file_author: Option<String>,
+    pub file_version: Option<String>,
+    pub file_tagline: Option<String>,
+    pub file_chapter_ids: Option<String>,
+}
@@ -1 +1,3 @@
 pub mod addon;
+pub mod profile;
+pub mod scan;
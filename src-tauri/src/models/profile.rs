@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Profile {
+    pub id: i64,
+    pub name: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+/// The addons enabled by a profile
+pub struct ProfileItems {
+    /// Managed addons, by their id (file hash)
+    pub ids: Vec<String>,
+    /// Workshop folder items, by their workshop id
+    pub workshop_ids: Vec<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileEntry {
+    #[serde(flatten)]
+    pub profile: Profile,
+    pub items: ProfileItems,
+}
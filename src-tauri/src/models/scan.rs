@@ -0,0 +1,86 @@
+use serde::Serialize;
+use sqlx::FromRow;
+use std::time::Duration;
+
+#[derive(Default, Debug, Serialize)]
+/// Counts of files a scan went through
+pub struct ScanCounter {
+    pub total: u32,
+    pub added: u32,
+    pub updated: u32,
+    /// Files that were unchanged since last scan, and not rescanned
+    pub skipped: u32,
+    pub errors: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ScanStatus {
+    Running,
+    Complete,
+    Aborted,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ScanRecord {
+    pub id: i64,
+    /// unix seconds
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    pub speed: String,
+    pub status: ScanStatus,
+    pub total: i64,
+    pub added: i64,
+    pub updated: i64,
+    pub skipped: i64,
+    pub failed: i64,
+    pub aborted_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+/// A file that could not be scanned, or whose entry could not be stored
+pub struct ScanError {
+    pub scan_id: i64,
+    pub filename: String,
+    /// Set if file is in the workshop folder
+    pub workshop_id: Option<i64>,
+    pub error: String,
+}
+
+#[derive(Default, Debug)]
+/// Time a scan spent on each step. Hashing and parsing are summed over all worker threads
+pub struct ScanTimings {
+    /// Files processed, including ones that failed
+    pub files: u32,
+    pub hash: Duration,
+    pub parse: Duration,
+    /// Time spent in transactions storing the results
+    pub db: Duration,
+}
+impl ScanTimings {
+    pub fn report(&self, elapsed: Duration) -> ScanBenchmark {
+        let secs = elapsed.as_secs_f64();
+        ScanBenchmark {
+            files: self.files,
+            elapsed_ms: elapsed.as_millis() as u64,
+            files_per_sec: if secs > 0.0 { self.files as f64 / secs } else { 0.0 },
+            hash_ms: self.hash.as_millis() as u64,
+            parse_ms: self.parse.as_millis() as u64,
+            db_ms: self.db.as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Result of a benchmark scan
+pub struct ScanBenchmark {
+    pub files: u32,
+    pub elapsed_ms: u64,
+    pub files_per_sec: f64,
+    /// Summed over worker threads, so can be more than elapsed
+    pub hash_ms: u64,
+    /// Summed over worker threads, so can be more than elapsed
+    pub parse_ms: u64,
+    pub db_ms: u64,
+}
@@ -1,6 +1,44 @@
+use l4d2_addon_parser::addon_list::AddonList;
+use serde::Serialize;
+use std::path::PathBuf;
+
 /* TODO:
 - uninstall fn
 - unsub fn
 - delete fn
 - get addon list entry
  */
+
+#[derive(Serialize, Clone)]
+#[serde(tag = "result")]
+#[serde(rename_all = "lowercase")]
+pub enum ItemResult {
+    Ok { filename: String },
+    Error { filename: String, error: String },
+}
+impl ItemResult {
+    pub fn ok(filename: String) -> Self {
+        ItemResult::Ok { filename }
+    }
+    pub fn error(filename: String, error: String) -> Self {
+        ItemResult::Error { filename, error }
+    }
+}
+
+/// Enables or disables addons in addonlist.txt, by their filename
+pub fn set_addons_state(addonlist_path: &PathBuf, filenames: Vec<String>, state: bool) -> Result<Vec<ItemResult>, String> {
+    let mut list =
+        AddonList::new(addonlist_path).map_err(|e| format!("failed to check state: {}", e))?;
+    let results = filenames
+        .into_iter()
+        .map(
+            |filename| match list.set_enabled(filename.to_string(), state) {
+                Ok(()) => ItemResult::ok(filename),
+                Err(err) => ItemResult::error(filename, err.to_string()),
+            },
+        )
+        .collect();
+    list.save()
+        .map_err(|e| format!("failed to save addonlist.txt: {}", e))?;
+    Ok(results)
+}
@@ -1,10 +1,12 @@
+use crate::modules::server::get_install_root;
+use crate::scan::ScanSpeed;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use serde_with::serde_as;
 use steam_workshop_api::SteamWorkshop;
-use tauri::async_runtime::Mutex;
+use tokio::sync::Mutex;
 
 #[derive(Serialize, Clone)]
 pub struct StaticData {
@@ -14,6 +16,7 @@ pub struct StaticData {
     pub os_arch: String,
     pub is_prod: bool
 }
+#[cfg(feature = "gui")]
 impl StaticData {
     pub fn new(app: &tauri::App) -> Self {
         Self {
@@ -28,6 +31,27 @@ impl StaticData {
 
 pub type AppConfigContainer = Mutex<AppConfig>;
 
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+/// How an imported config is applied to the current config
+pub enum ConfigImportMode {
+    /// Only fills in settings that are not set locally
+    #[default]
+    Merge,
+    /// Imported settings overwrite local settings
+    Replace,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+/// A game or dedicated server install, each has its own database
+pub struct Installation {
+    pub id: u32,
+    pub name: String,
+    pub addons_folder: PathBuf,
+    #[serde(default)]
+    pub server_mode: bool,
+}
+
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
 #[serde_as]
 pub struct AppConfig {
@@ -41,11 +65,45 @@ pub struct AppConfig {
     #[serde(default = "default_as_true")]
     pub startup_scan: bool,
     #[serde(default)]
-    pub startup_telemetry: bool
+    pub startup_telemetry: bool,
+    /// Speed of scans the user starts
+    #[serde(default)]
+    pub scan_speed: ScanSpeed,
+    /// Hours between scheduled scans, None to not scan periodically. Off unless the user turns it on
+    #[serde(default)]
+    pub scan_interval_hours: Option<u32>,
+    /// Local hour from which scheduled scans can start, such as overnight. Needs scan_quiet_end
+    #[serde(default)]
+    pub scan_quiet_start: Option<u8>,
+    /// Local hour scheduled scans stop starting at, can be before scan_quiet_start to wrap past midnight
+    #[serde(default)]
+    pub scan_quiet_end: Option<u8>,
+    /// Speed of startup and scheduled scans
+    #[serde(default = "default_scheduled_scan_speed")]
+    pub scheduled_scan_speed: ScanSpeed,
+
+    /// Addons folder is of a dedicated server (srcds), which has no workshop subscriptions to migrate
+    #[serde(default)]
+    pub server_mode: bool,
+    /// Workshop collection the server hosts, for host_workshop_collection
+    #[serde(default)]
+    pub server_collection_id: Option<i64>,
+    /// FastDL url clients download addons from, for sv_downloadurl
+    #[serde(default)]
+    pub server_download_url: Option<String>,
+
+    /// Installs that are managed, addons_folder and server_mode are always of the active installation
+    #[serde(default)]
+    pub installations: Vec<Installation>,
+    #[serde(default)]
+    pub active_installation: Option<u32>,
 }
 fn default_as_true() -> bool {
     true
 }
+fn default_scheduled_scan_speed() -> ScanSpeed {
+    ScanSpeed::Background
+}
 
 impl AppConfig {
     pub fn load(path_buf: PathBuf) -> Self {
@@ -63,6 +121,7 @@ impl AppConfig {
             }
         };
         config._save_path = path_buf;
+        config.sync_active_installation();
         config
     }
 
@@ -83,6 +142,45 @@ impl AppConfig {
         (steam, false)
     }
 
+    pub fn get_installation(&self, id: u32) -> Option<&Installation> {
+        self.installations.iter().find(|install| install.id == id)
+    }
+
+    /// Records addons_folder and server_mode to the active installation. If there is none,
+    /// such as configs from before installations existed, the first installation is created
+    pub fn sync_active_installation(&mut self) {
+        let Some(addons_folder) = self.addons_folder.clone() else { return };
+        let server_mode = self.server_mode;
+        let active = self.active_installation;
+        match self.installations.iter_mut().find(|install| Some(install.id) == active) {
+            Some(install) => {
+                install.addons_folder = addons_folder;
+                install.server_mode = server_mode;
+            }
+            None => {
+                let id = self.installations.iter().map(|install| install.id).max().unwrap_or(0) + 1;
+                let name = get_install_root(&addons_folder)
+                    .and_then(|root| root.file_name().map(|name| name.to_string_lossy().to_string()))
+                    .unwrap_or_else(|| format!("Installation {}", id));
+                debug!("created installation {} \"{}\"", id, name);
+                self.installations.push(Installation { id, name, addons_folder, server_mode });
+                self.active_installation = Some(id);
+            }
+        }
+    }
+
+    /// Makes installation the active one, so its addons folder is used
+    pub fn set_active_installation(&mut self, id: u32) -> Result<(), String> {
+        let install = self
+            .get_installation(id)
+            .cloned()
+            .ok_or_else(|| format!("installation {} does not exist", id))?;
+        self.addons_folder = Some(install.addons_folder);
+        self.server_mode = install.server_mode;
+        self.active_installation = Some(id);
+        Ok(())
+    }
+
     pub fn validate(&self, new_config: &Self) -> Result<(), String> {
         if let Some(key) = &new_config.steam_apikey {
             if key.len() > 0 && key.len() != 32 {
@@ -96,9 +194,71 @@ impl AppConfig {
                 return Err("Addons folder must be a directory".to_string());
             }
         }
+        if let Some(url) = &new_config.server_download_url {
+            if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err("Download URL must start with http:// or https://".to_string());
+            }
+        }
+        for speed in [new_config.scan_speed, new_config.scheduled_scan_speed] {
+            if let ScanSpeed::Custom { threads, max_mb_per_sec } = speed {
+                if threads == 0 {
+                    return Err("Scan must use at least one thread".to_string());
+                }
+                if max_mb_per_sec == Some(0) {
+                    return Err("Scan read limit must be at least 1 MB/s".to_string());
+                }
+            }
+        }
+        if new_config.scan_interval_hours == Some(0) {
+            return Err("Scan interval must be at least 1 hour".to_string());
+        }
+        match (new_config.scan_quiet_start, new_config.scan_quiet_end) {
+            (Some(start), Some(end)) if start > 23 || end > 23 => {
+                return Err("Quiet hours must be between 0 and 23".to_string());
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                return Err("Quiet hours need both a start and an end".to_string());
+            }
+            _ => {}
+        }
 
         Ok(())
     }
+    /// Applies settings from an imported config. The local addons folder and installations are always kept if set,
+    /// as the imported paths are likely from another machine
+    pub fn import(&mut self, imported: Self, mode: ConfigImportMode) {
+        if self.addons_folder.is_none() {
+            self.addons_folder = imported.addons_folder.filter(|folder| folder.is_dir());
+        }
+        match mode {
+            ConfigImportMode::Merge => {
+                if self.steam_apikey.is_none() {
+                    self.steam_apikey = imported.steam_apikey;
+                }
+                if self.server_collection_id.is_none() {
+                    self.server_collection_id = imported.server_collection_id;
+                }
+                if self.server_download_url.is_none() {
+                    self.server_download_url = imported.server_download_url;
+                }
+            }
+            ConfigImportMode::Replace => {
+                self.steam_apikey = imported.steam_apikey;
+                self.startup_scan = imported.startup_scan;
+                self.startup_telemetry = imported.startup_telemetry;
+                self.scan_speed = imported.scan_speed;
+                self.scan_interval_hours = imported.scan_interval_hours;
+                self.scan_quiet_start = imported.scan_quiet_start;
+                self.scan_quiet_end = imported.scan_quiet_end;
+                self.scheduled_scan_speed = imported.scheduled_scan_speed;
+                self.server_mode = imported.server_mode;
+                self.server_collection_id = imported.server_collection_id;
+                self.server_download_url = imported.server_download_url;
+            }
+        }
+        self.sync_active_installation();
+    }
+
     /// Tries to replace config with a new config, after the new settings are validated
     pub fn replace(&mut self, new_config: Self) -> Result<(), String> {
         self.validate(&new_config)?;
@@ -106,6 +266,15 @@ impl AppConfig {
         self.addons_folder = new_config.addons_folder;
         self.startup_scan = new_config.startup_scan;
         self.startup_telemetry = new_config.startup_telemetry;
+        self.scan_speed = new_config.scan_speed;
+        self.scan_interval_hours = new_config.scan_interval_hours;
+        self.scan_quiet_start = new_config.scan_quiet_start;
+        self.scan_quiet_end = new_config.scan_quiet_end;
+        self.scheduled_scan_speed = new_config.scheduled_scan_speed;
+        self.server_mode = new_config.server_mode;
+        self.server_collection_id = new_config.server_collection_id;
+        self.server_download_url = new_config.server_download_url.filter(|url| !url.is_empty());
+        self.sync_active_installation();
         Ok(())
     }
 }
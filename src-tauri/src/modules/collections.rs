@@ -0,0 +1,469 @@
+use crate::modules::store::{AddonStorage, FileHash};
+use crate::scan::helpers::get_workshop_folder_ws_ids;
+use log::{debug, info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use steam_workshop_api::WorkshopItem;
+
+static COLLECTION_URL_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[?&]id=(\d+)").unwrap());
+const STEAM_API_URL: &str = "https://api.steampowered.com";
+/// Most items GetPublishedFileDetails returns at a time
+const ITEMS_PER_REQUEST: usize = 100;
+/// EWorkshopFileType of a collection
+const FILETYPE_COLLECTION: u32 = 2;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CollectionChild {
+    pub publishedfileid: String,
+    #[serde(default)]
+    pub sortorder: u32,
+    #[serde(default)]
+    pub filetype: u32,
+}
+
+#[derive(Deserialize)]
+struct CollectionDetails {
+    publishedfileid: String,
+    #[serde(default)]
+    children: Vec<CollectionChild>,
+}
+
+#[derive(Deserialize)]
+struct CollectionDetailsResponse {
+    #[serde(default)]
+    collectiondetails: Vec<CollectionDetails>,
+}
+
+#[derive(Deserialize)]
+struct PublishedFileDetailsResponse {
+    /// Items that could not be found only have an id and result, so are parsed one by one
+    #[serde(default)]
+    publishedfiledetails: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct SteamResponse<T> {
+    response: T,
+}
+
+/// Where collections and workshop item info are fetched from
+pub trait WorkshopApi {
+    /// Returns the children of each collection, keyed by collection id
+    fn get_collection_children(
+        &self,
+        ids: &[i64],
+    ) -> impl Future<Output = Result<HashMap<i64, Vec<CollectionChild>>, String>> + Send;
+    /// Returns info of the given workshop items, skipping any that could not be found
+    fn get_items(&self, ids: Vec<i64>) -> impl Future<Output = Vec<WorkshopItem>> + Send;
+}
+
+/// Steam's web api, collection details do not need an api key
+pub struct SteamApi {
+    client: reqwest::Client,
+    base_url: String,
+}
+impl SteamApi {
+    pub fn new() -> Self {
+        Self::with_base_url(STEAM_API_URL)
+    }
+
+    /// Uses another server in place of api.steampowered.com
+    pub fn with_base_url(base_url: &str) -> Self {
+        SteamApi {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn get_item_details(&self, ids: &[i64]) -> Result<Vec<WorkshopItem>, String> {
+        let mut form = vec![("itemcount".to_string(), ids.len().to_string())];
+        for (i, id) in ids.iter().enumerate() {
+            form.push((format!("publishedfileids[{}]", i), id.to_string()));
+        }
+        let body = self
+            .client
+            .post(format!("{}/ISteamRemoteStorage/GetPublishedFileDetails/v1/", self.base_url))
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("get workshop items: {}", e))?
+            .text()
+            .await
+            .map_err(|e| format!("get workshop items: {}", e))?;
+        let details: SteamResponse<PublishedFileDetailsResponse> =
+            serde_json::from_str(&body).map_err(|e| format!("bad workshop items response: {}", e))?;
+        Ok(details
+            .response
+            .publishedfiledetails
+            .into_iter()
+            .filter_map(|item| serde_json::from_value::<WorkshopItem>(item).ok())
+            .collect())
+    }
+}
+impl WorkshopApi for SteamApi {
+    async fn get_collection_children(&self, ids: &[i64]) -> Result<HashMap<i64, Vec<CollectionChild>>, String> {
+        let mut form = vec![("collectioncount".to_string(), ids.len().to_string())];
+        for (i, id) in ids.iter().enumerate() {
+            form.push((format!("publishedfileids[{}]", i), id.to_string()));
+        }
+        let body = self
+            .client
+            .post(format!("{}/ISteamRemoteStorage/GetCollectionDetails/v1/", self.base_url))
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("get collection details: {}", e))?
+            .text()
+            .await
+            .map_err(|e| format!("get collection details: {}", e))?;
+        let details: SteamResponse<CollectionDetailsResponse> =
+            serde_json::from_str(&body).map_err(|e| format!("bad collection details response: {}", e))?;
+        Ok(details
+            .response
+            .collectiondetails
+            .into_iter()
+            .filter_map(|collection| Some((collection.publishedfileid.parse::<i64>().ok()?, collection.children)))
+            .collect())
+    }
+
+    async fn get_items(&self, ids: Vec<i64>) -> Vec<WorkshopItem> {
+        let mut items = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(ITEMS_PER_REQUEST) {
+            match self.get_item_details(chunk).await {
+                Ok(details) => items.extend(details),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        items
+    }
+}
+
+/// Gets collection id from a workshop url, or the id itself
+pub fn parse_collection_id(input: &str) -> Result<i64, String> {
+    let input = input.trim();
+    if let Ok(id) = input.parse::<i64>() {
+        return Ok(id);
+    }
+    COLLECTION_URL_REGEX
+        .captures(input)
+        .and_then(|capture| capture[1].parse::<i64>().ok())
+        .ok_or_else(|| format!("\"{}\" is not a workshop collection url or id", input))
+}
+
+enum CollectionEntry {
+    Item(i64),
+    Collection(i64),
+}
+
+/// Returns every item in collection and any collections nested in it, in collection order
+pub async fn resolve_collection(api: &impl WorkshopApi, id: i64) -> Result<Vec<i64>, String> {
+    let mut children = api.get_collection_children(&[id]).await?;
+    if !children.contains_key(&id) {
+        return Err(format!("collection {} does not exist", id));
+    }
+    // Fetch nested collections level by level, they are expanded in place after
+    let mut seen: HashSet<i64> = HashSet::from([id]);
+    let mut collections: HashMap<i64, Vec<CollectionEntry>> = HashMap::new();
+    let mut pending = vec![id];
+    while !pending.is_empty() {
+        let mut next = Vec::new();
+        for collection in pending {
+            let mut list = children.remove(&collection).unwrap_or_default();
+            list.sort_by_key(|child| child.sortorder);
+            let entries = list
+                .into_iter()
+                .filter_map(|child| {
+                    let child_id = child.publishedfileid.parse::<i64>().ok()?;
+                    if child.filetype != FILETYPE_COLLECTION {
+                        return Some(CollectionEntry::Item(child_id));
+                    }
+                    // Collections can include each other, only expand once
+                    if !seen.insert(child_id) {
+                        return None;
+                    }
+                    next.push(child_id);
+                    Some(CollectionEntry::Collection(child_id))
+                })
+                .collect();
+            collections.insert(collection, entries);
+        }
+        if !next.is_empty() {
+            debug!("fetching {} nested collections", next.len());
+            children = api.get_collection_children(&next).await?;
+        }
+        pending = next;
+    }
+
+    let mut items: Vec<i64> = Vec::new();
+    let mut added: HashSet<i64> = HashSet::new();
+    let mut stack = vec![collections.remove(&id).unwrap_or_default().into_iter()];
+    while let Some(entries) = stack.last_mut() {
+        match entries.next() {
+            Some(CollectionEntry::Collection(nested)) => {
+                stack.push(collections.remove(&nested).unwrap_or_default().into_iter())
+            }
+            Some(CollectionEntry::Item(item)) => {
+                if added.insert(item) {
+                    items.push(item);
+                }
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    Ok(items)
+}
+
+#[derive(Serialize, Debug)]
+pub struct CollectionImport {
+    /// Id of the created profile
+    pub profile_id: i64,
+    pub name: String,
+    /// Items in the workshop folder
+    pub subscribed: Vec<i64>,
+    /// Items with a managed copy
+    pub managed: Vec<i64>,
+    /// Items that are neither subscribed to or managed
+    pub missing: Vec<i64>,
+}
+
+/// Creates a profile enabling every item of a workshop collection, and stores the items' workshop info.
+/// Subscribed items are preferred over managed copies, missing items are kept as workshop items so the
+/// profile reports them as missing when applied. Uses collection's title if name is not given
+pub async fn import_collection(
+    api: &impl WorkshopApi,
+    addons: &AddonStorage,
+    addons_folder: &PathBuf,
+    collection: &str,
+    name: Option<String>,
+) -> Result<CollectionImport, String> {
+    let collection_id = parse_collection_id(collection)?;
+    let ids = resolve_collection(api, collection_id).await?;
+    debug!("collection {} has {} items", collection_id, ids.len());
+
+    let mut fetch_ids = ids.clone();
+    fetch_ids.push(collection_id);
+    let items = api.get_items(fetch_ids).await;
+    let name = match name {
+        Some(name) => name,
+        None => items
+            .iter()
+            .find(|item| item.publishedfileid == collection_id.to_string())
+            .map(|item| item.title.clone())
+            .unwrap_or_else(|| format!("Collection {}", collection_id)),
+    };
+    // Collection itself is not an addon
+    let items: Vec<WorkshopItem> = items
+        .into_iter()
+        .filter(|item| item.publishedfileid != collection_id.to_string())
+        .collect();
+    addons.add_workshop_items(items).await.map_err(|e| e.to_string())?;
+
+    let workshop_folder_ids: HashSet<i64> = get_workshop_folder_ws_ids(addons_folder).into_iter().collect();
+    let managed_hashes: HashMap<i64, FileHash> = addons
+        .list_workshop_linked()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|(hash, _, _, workshop_id)| (workshop_id, hash))
+        .collect();
+
+    let (mut subscribed, mut managed, mut missing) = (Vec::new(), Vec::new(), Vec::new());
+    let mut hashes: Vec<FileHash> = Vec::new();
+    for id in ids {
+        if workshop_folder_ids.contains(&id) {
+            subscribed.push(id);
+        } else if let Some(hash) = managed_hashes.get(&id) {
+            hashes.push(hash.clone());
+            managed.push(id);
+        } else {
+            missing.push(id);
+        }
+    }
+    let workshop_ids: Vec<i64> = subscribed.iter().chain(missing.iter()).copied().collect();
+    let profile_id = addons
+        .create_profile(&name, &hashes, &workshop_ids)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!(
+        "Imported collection {} as profile \"{}\" ({} subscribed, {} managed, {} missing)",
+        collection_id, name, subscribed.len(), managed.len(), missing.len()
+    );
+    Ok(CollectionImport { profile_id, name, subscribed, managed, missing })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::worker::{async_process_file, scan_file};
+    use crate::util::runtime::block_on;
+    use crate::util::test_util::{addon_vpk, temp_dir};
+    use sqlx::SqlitePool;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    /// Serves collections and items from memory
+    #[derive(Default)]
+    struct MockApi {
+        collections: HashMap<i64, Vec<CollectionChild>>,
+        titles: HashMap<i64, &'static str>,
+    }
+    impl WorkshopApi for MockApi {
+        async fn get_collection_children(&self, ids: &[i64]) -> Result<HashMap<i64, Vec<CollectionChild>>, String> {
+            Ok(ids
+                .iter()
+                .filter_map(|id| Some((*id, self.collections.get(id)?.clone())))
+                .collect())
+        }
+
+        async fn get_items(&self, ids: Vec<i64>) -> Vec<WorkshopItem> {
+            ids.into_iter()
+                .map(|id| {
+                    let title = self.titles.get(&id).copied().unwrap_or("Item");
+                    serde_json::from_value(serde_json::json!({
+                        "publishedfileid": id.to_string(),
+                        "result": 1,
+                        "creator": "1",
+                        "creator_app_id": 550,
+                        "consumer_app_id": 550,
+                        "filename": "",
+                        "file_size": 0,
+                        "file_url": "",
+                        "hcontent_file": "0",
+                        "preview_url": "",
+                        "hcontent_preview": "0",
+                        "title": title,
+                        "description": "",
+                        "time_created": 0,
+                        "time_updated": 0,
+                        "visibility": 0,
+                        "banned": 0,
+                        "ban_reason": "",
+                        "subscriptions": 0,
+                        "favorited": 0,
+                        "lifetime_subscriptions": 0,
+                        "lifetime_favorited": 0,
+                        "views": 0,
+                        "tags": []
+                    }))
+                    .unwrap()
+                })
+                .collect()
+        }
+    }
+
+    fn item(id: i64, sortorder: u32) -> CollectionChild {
+        CollectionChild { publishedfileid: id.to_string(), sortorder, filetype: 0 }
+    }
+
+    fn collection(id: i64, sortorder: u32) -> CollectionChild {
+        CollectionChild { publishedfileid: id.to_string(), sortorder, filetype: FILETYPE_COLLECTION }
+    }
+
+    #[test]
+    fn parses_collection_id() {
+        assert_eq!(parse_collection_id(" 123 "), Ok(123));
+        assert_eq!(
+            parse_collection_id("https://steamcommunity.com/sharedfiles/filedetails/?id=456&searchtext="),
+            Ok(456)
+        );
+        assert_eq!(
+            parse_collection_id("https://steamcommunity.com/workshop/filedetails/?l=english&id=789"),
+            Ok(789)
+        );
+        assert!(parse_collection_id("https://steamcommunity.com/workshop/").is_err());
+    }
+
+    #[test]
+    fn resolves_nested_and_cyclic_collections() {
+        let api = MockApi {
+            collections: HashMap::from([
+                (1, vec![item(10, 2), collection(2, 1), item(11, 3)]),
+                (2, vec![item(20, 0), item(10, 1), collection(3, 2)]),
+                // Includes both collections it is in
+                (3, vec![collection(1, 0), item(30, 1), collection(2, 2)]),
+            ]),
+            ..Default::default()
+        };
+        block_on(async {
+            // Nested collections are expanded in place, each item once
+            assert_eq!(resolve_collection(&api, 1).await, Ok(vec![20, 10, 30, 11]));
+            assert_eq!(resolve_collection(&api, 3).await, Ok(vec![10, 11, 30, 20]));
+            assert!(resolve_collection(&api, 4).await.is_err());
+        });
+    }
+
+    #[test]
+    fn import_splits_subscribed_managed_and_missing() {
+        let dir = temp_dir("collection");
+        let addons_folder = dir.join("addons");
+        let store_folder = dir.join("store");
+        std::fs::create_dir_all(addons_folder.join("workshop")).unwrap();
+        std::fs::write(addons_folder.join("workshop").join("10.vpk"), addon_vpk("Subscribed", "1.0")).unwrap();
+        std::fs::write(addons_folder.join("subscribed.vpk"), addon_vpk("Subscribed Copy", "1.0")).unwrap();
+        std::fs::write(addons_folder.join("managed.vpk"), addon_vpk("Managed", "1.0")).unwrap();
+        let api = MockApi {
+            collections: HashMap::from([(1, vec![item(10, 0), item(11, 1), item(12, 2)])]),
+            titles: HashMap::from([(1, "My Collection")]),
+        };
+
+        block_on(async {
+            let storage = AddonStorage::new(store_folder.clone()).await.unwrap();
+            storage.run_migrations().await.unwrap();
+            let addons = Arc::new(RwLock::new(storage));
+            for filename in ["subscribed.vpk", "managed.vpk"] {
+                let file = scan_file(addons_folder.join(filename), None).unwrap();
+                async_process_file(file, addons.clone(), 1).await.unwrap();
+            }
+
+            // Link both copies, the subscribed item is preferred over its copy
+            let db = SqlitePool::connect(&format!("sqlite://{}", store_folder.join("addon-manager.db").display()))
+                .await
+                .unwrap();
+            for (filename, workshop_id) in [("subscribed.vpk", 10), ("managed.vpk", 11)] {
+                sqlx::query("UPDATE addons SET workshop_id = ? WHERE filename = ?")
+                    .bind(workshop_id)
+                    .bind(filename)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            }
+            let managed_hash = addons
+                .read()
+                .await
+                .list_hash_filenames()
+                .await
+                .unwrap()
+                .into_iter()
+                .find(|(_, filename)| filename.as_deref() == Some("managed.vpk"))
+                .map(|(hash, _)| hash)
+                .unwrap();
+
+            let storage = addons.read().await;
+            let import = import_collection(&api, &storage, &addons_folder, "1", None).await.unwrap();
+            assert_eq!(import.name, "My Collection");
+            assert_eq!(import.subscribed, vec![10]);
+            assert_eq!(import.managed, vec![11]);
+            assert_eq!(import.missing, vec![12]);
+
+            let items = storage.get_profile_items(import.profile_id).await.unwrap();
+            assert_eq!(items.ids, vec![managed_hash.to_string()]);
+            let mut workshop_ids = items.workshop_ids;
+            workshop_ids.sort();
+            assert_eq!(workshop_ids, vec![10, 12]);
+            // The collection itself is not stored as an item
+            assert!(!storage.list_workshop_ids().await.unwrap().contains(&1));
+
+            db.close().await;
+            storage.close().await;
+        });
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,137 @@
+use crate::modules::store::{AddonStorage, FileHash};
+use l4d2_addon_parser::addon_list::AddonList;
+use log::debug;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictCategory {
+    Models,
+    Materials,
+    Sound,
+    Scripts,
+    Other,
+}
+impl ConflictCategory {
+    fn from_path(path: &str) -> Self {
+        let root = path.split('/').next().unwrap_or_default().to_lowercase();
+        match root.as_str() {
+            "models" => ConflictCategory::Models,
+            "materials" => ConflictCategory::Materials,
+            "sound" => ConflictCategory::Sound,
+            "scripts" => ConflictCategory::Scripts,
+            _ => ConflictCategory::Other,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ConflictAddon {
+    /// Hash of addon, same as entry id
+    pub id: String,
+    pub filename: String,
+    pub title: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConflictCategoryGroup {
+    pub category: ConflictCategory,
+    pub paths: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+/// Files that two enabled addons both replace
+pub struct AddonConflict {
+    /// Addon whose files are used in game, under current load order
+    pub winner: ConflictAddon,
+    /// Addon whose files are overridden
+    pub loser: ConflictAddon,
+    /// Total number of overlapping files
+    pub total: u32,
+    pub categories: Vec<ConflictCategoryGroup>,
+}
+
+/// Returns entry names of addonlist.txt in the order they are listed, which is the order the game loads them in
+fn get_load_order(addonlist_path: &PathBuf) -> Result<Vec<String>, String> {
+    let contents = std::fs::read_to_string(addonlist_path)
+        .map_err(|e| format!("failed to read addonlist.txt: {}", e))?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| {
+            // Entries are in the form of "name.vpk" "1"
+            let mut parts = line.split('"').skip(1).step_by(2);
+            let name = parts.next()?;
+            parts.next()?;
+            Some(name.to_lowercase())
+        })
+        .collect())
+}
+
+/// Finds files that are replaced by more than one enabled managed addon, grouped by addon pair.
+/// Addons loaded first take priority, addons not in addonlist.txt are loaded after in alphabetical order
+pub async fn find_conflicts(addons: &AddonStorage, addons_folder: &PathBuf) -> Result<Vec<AddonConflict>, String> {
+    let addonlist_path = addons_folder.parent().unwrap().join("addonlist.txt");
+    let list = AddonList::new(&addonlist_path).map_err(|e| format!("failed to read addonlist.txt: {}", e))?;
+    let load_order: HashMap<String, usize> = get_load_order(&addonlist_path)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| (name, i))
+        .collect();
+
+    let enabled: HashMap<FileHash, ConflictAddon> = addons
+        .list_hash_filename_titles()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|(_, filename, _)| list.is_enabled(filename))
+        .map(|(hash, filename, title)| {
+            let id = hash.to_string();
+            (hash, ConflictAddon { id, filename, title })
+        })
+        .collect();
+    let hashes: Vec<FileHash> = enabled.keys().cloned().collect();
+    let overlaps = addons
+        .list_file_overlaps(&hashes)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Position in load order, unlisted addons after by filename
+    let position = |addon: &ConflictAddon| {
+        let listed = load_order.get(&addon.filename.to_lowercase()).copied();
+        (listed.is_none(), listed, addon.filename.to_lowercase())
+    };
+
+    let mut pairs: HashMap<(FileHash, FileHash), Vec<String>> = HashMap::new();
+    for (a, b, path) in overlaps {
+        pairs.entry((a, b)).or_default().push(path);
+    }
+    let mut conflicts: Vec<AddonConflict> = pairs
+        .into_iter()
+        .map(|((a, b), paths)| {
+            let (a, b) = (&enabled[&a], &enabled[&b]);
+            let (winner, loser) = if position(a) <= position(b) { (a, b) } else { (b, a) };
+            let total = paths.len() as u32;
+            let mut by_category: HashMap<ConflictCategory, Vec<String>> = HashMap::new();
+            for path in paths {
+                by_category.entry(ConflictCategory::from_path(&path)).or_default().push(path);
+            }
+            let mut categories: Vec<ConflictCategoryGroup> = by_category
+                .into_iter()
+                .map(|(category, paths)| ConflictCategoryGroup { category, paths })
+                .collect();
+            categories.sort_by_key(|group| group.category);
+            AddonConflict {
+                winner: winner.clone(),
+                loser: loser.clone(),
+                total,
+                categories,
+            }
+        })
+        .collect();
+    // Most overlapping files first
+    conflicts.sort_by(|a, b| b.total.cmp(&a.total));
+    debug!("found {} addon pairs with conflicting files", conflicts.len());
+    Ok(conflicts)
+}
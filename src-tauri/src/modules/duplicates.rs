@@ -0,0 +1,236 @@
+use crate::modules::addons::ItemResult;
+use crate::modules::store::{AddonStorageContainer, FileHash};
+use crate::scan::helpers::get_workshop_folder_ws_ids;
+use crate::scan::worker::{async_process_file, scan_file};
+use crate::util::{edit_addon_list, get_file_size};
+use l4d2_addon_parser::L4D2Addon;
+use l4d2_addon_parser::addon_list::AddonList;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sqlx::__rt::spawn_blocking;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+/// A vpk in either the addons folder or the workshop folder
+pub struct AddonFileRef {
+    pub filename: String,
+    /// Is file in the workshop folder
+    pub workshop: bool,
+}
+impl AddonFileRef {
+    fn path(&self, addons_folder: &PathBuf) -> PathBuf {
+        if self.workshop {
+            addons_folder.join("workshop").join(&self.filename)
+        } else {
+            addons_folder.join(&self.filename)
+        }
+    }
+
+    /// Name as it appears in addonlist.txt
+    fn list_name(&self) -> String {
+        if self.workshop {
+            format!("workshop\\{}", self.filename)
+        } else {
+            self.filename.clone()
+        }
+    }
+
+    /// Filename must be a plain file name, so only files in the addon folders can be touched
+    fn validate(&self) -> Result<(), String> {
+        if self.filename.is_empty() || self.filename.contains(['/', '\\']) || self.filename == ".." {
+            return Err(format!("invalid filename \"{}\"", self.filename));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    /// Files have the same content
+    SameHash,
+    /// Files are copies of the same workshop item
+    SameWorkshopId,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DuplicateFile {
+    #[serde(flatten)]
+    pub file: AddonFileRef,
+    pub file_size: i64,
+    /// Is file enabled in addonlist.txt, None if it could not be read
+    pub enabled: Option<bool>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DuplicateGroup {
+    pub reason: DuplicateReason,
+    pub workshop_id: Option<i64>,
+    /// Content hash, if grouped by hash
+    pub hash: Option<String>,
+    pub files: Vec<DuplicateFile>,
+}
+
+fn hash_file(path: &PathBuf) -> Result<FileHash, String> {
+    let mut addon = L4D2Addon::from_path(path).map_err(|e| format!("load addon: {}", e))?;
+    let hash = addon.hash_256().map_err(|e| format!("hash addon: {}", e))?;
+    Ok(FileHash(hash))
+}
+
+/// Finds managed (filename, workshop id) and workshop folder files that are copies of the same workshop item, or have the same content.
+/// Content is only hashed for files that share the same size
+pub fn find_duplicates(addons_folder: &PathBuf, managed: Vec<(String, Option<i64>)>) -> Vec<DuplicateGroup> {
+    let addon_list = AddonList::new(&addons_folder.parent().unwrap().join("addonlist.txt")).ok();
+    let workshop_folder_ids: HashSet<i64> = get_workshop_folder_ws_ids(addons_folder).into_iter().collect();
+
+    let mut files: Vec<(AddonFileRef, Option<i64>)> = managed
+        .into_iter()
+        .map(|(filename, workshop_id)| (AddonFileRef { filename, workshop: false }, workshop_id))
+        .collect();
+    files.extend(workshop_folder_ids.iter().map(|id| {
+        (AddonFileRef { filename: format!("{}.vpk", id), workshop: true }, Some(*id))
+    }));
+
+    let to_duplicate_file = |file: &AddonFileRef| DuplicateFile {
+        file: file.clone(),
+        file_size: file.path(addons_folder).metadata().map(|meta| get_file_size(&meta)).unwrap_or(0),
+        enabled: addon_list.as_ref().map(|list| list.is_enabled(&file.list_name())),
+    };
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+
+    // Group by workshop id
+    let mut by_workshop_id: HashMap<i64, Vec<&AddonFileRef>> = HashMap::new();
+    for (file, workshop_id) in &files {
+        if let Some(id) = workshop_id {
+            by_workshop_id.entry(*id).or_default().push(file);
+        }
+    }
+    let mut grouped: Vec<HashSet<&AddonFileRef>> = Vec::new();
+    for (id, group) in by_workshop_id.into_iter().filter(|(_, group)| group.len() > 1) {
+        grouped.push(group.iter().copied().collect());
+        groups.push(DuplicateGroup {
+            reason: DuplicateReason::SameWorkshopId,
+            workshop_id: Some(id),
+            hash: None,
+            files: group.into_iter().map(&to_duplicate_file).collect(),
+        });
+    }
+
+    // Group by content, only hashing files with same size
+    let mut by_size: HashMap<u64, Vec<&AddonFileRef>> = HashMap::new();
+    for (file, _) in &files {
+        if let Ok(meta) = file.path(addons_folder).metadata() {
+            by_size.entry(meta.len()).or_default().push(file);
+        }
+    }
+    for candidates in by_size.into_values().filter(|group| group.len() > 1) {
+        let mut by_hash: HashMap<FileHash, Vec<&AddonFileRef>> = HashMap::new();
+        for file in candidates {
+            match hash_file(&file.path(addons_folder)) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(file),
+                Err(e) => warn!("duplicates: {}: {}", file.filename, e),
+            }
+        }
+        for (hash, group) in by_hash.into_iter().filter(|(_, group)| group.len() > 1) {
+            // Skip if already reported as same workshop item
+            let set: HashSet<&AddonFileRef> = group.iter().copied().collect();
+            if grouped.iter().any(|existing| set.is_subset(existing)) {
+                continue;
+            }
+            groups.push(DuplicateGroup {
+                reason: DuplicateReason::SameHash,
+                workshop_id: None,
+                hash: Some(hash.to_string()),
+                files: group.into_iter().map(&to_duplicate_file).collect(),
+            });
+        }
+    }
+    debug!("found {} duplicate groups", groups.len());
+    groups
+}
+
+/// Keeps one file and moves the others to trash. If any removed file was enabled, the kept file is enabled.
+/// Entries of removed managed files are removed from db, the kept managed file takes over an entry with same content
+pub async fn resolve_duplicates(
+    addons: AddonStorageContainer,
+    addons_folder: &PathBuf,
+    keep: AddonFileRef,
+    remove: Vec<AddonFileRef>,
+) -> Result<Vec<ItemResult>, String> {
+    keep.validate()?;
+    for file in &remove {
+        file.validate()?;
+        if *file == keep {
+            return Err(format!("cannot keep and remove \"{}\"", file.filename));
+        }
+    }
+    let keep_path = keep.path(addons_folder);
+    if !keep_path.is_file() {
+        return Err(format!("file to keep \"{}\" does not exist", keep.filename));
+    }
+    // Scan kept file before anything is removed, so its entry can be updated after
+    let keep_data = if keep.workshop {
+        None
+    } else {
+        Some(spawn_blocking(move || scan_file(keep_path, None)).await?)
+    };
+
+    let addonlist_path = addons_folder.parent().unwrap().join("addonlist.txt");
+    let mut removed_managed: Vec<String> = Vec::new();
+    let results = edit_addon_list(&addonlist_path, |list| {
+        let mut any_enabled = false;
+        let results: Vec<ItemResult> = remove
+            .iter()
+            .map(|file| {
+                let list_name = file.list_name();
+                if let Err(e) = trash::delete(file.path(addons_folder)) {
+                    return ItemResult::error(list_name, e.to_string());
+                }
+                any_enabled |= list.is_enabled(&list_name);
+                list.set_enabled(list_name.clone(), false).ok();
+                if !file.workshop {
+                    removed_managed.push(file.filename.clone());
+                }
+                ItemResult::ok(list_name)
+            })
+            .collect();
+        if any_enabled {
+            if let Err(e) = list.set_enabled(keep.list_name(), true) {
+                warn!("duplicates: failed to enable {}: {}", keep.filename, e);
+            }
+        }
+        results
+    })?;
+
+    if let Some(keep_data) = keep_data {
+        // Moves an entry with the same content over to the kept filename, keeping its tags
+        let scan_id = addons.read().await.current_scan_id().await.map_err(|e| e.to_string())?;
+        async_process_file(keep_data, addons.clone(), scan_id)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    if !removed_managed.is_empty() {
+        let addons = addons.read().await;
+        addons
+            .delete_filenames(removed_managed)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    if remove.iter().any(|file| file.workshop) {
+        debug!("workshop file removed, marking workshop ids");
+        let workshop_folder_ids = get_workshop_folder_ws_ids(addons_folder);
+        let addons = addons.read().await;
+        addons
+            .delete_workshop_files_except(&workshop_folder_ids)
+            .await
+            .map_err(|e| e.to_string())?;
+        addons
+            .mark_workshop_ids(workshop_folder_ids)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    info!("Resolved duplicates of {}, removed {} files", keep.filename, remove.len());
+    Ok(results)
+}
@@ -1,18 +1,31 @@
-use log::{debug, info};
+use crate::modules::store::{AddonStorage, FileHash};
+use crate::modules::vpk::{VpkDirectory, VpkFile, is_safe_path};
+use bzip2::write::BzEncoder;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, read_dir};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Instant;
-use tauri::{App, AppHandle, Emitter, Manager};
+#[cfg(feature = "gui")]
+use tauri::AppHandle;
+#[cfg(feature = "gui")]
 use tauri_plugin_dialog::DialogExt;
 use zip::result::ZipError;
 use zip::write::SimpleFileOptions;
 use zip::{CompressionMethod, ZipWriter};
 use crate::util::defs::ProgressPayload;
+use crate::util::events::EventSink;
 
-/// Export the app. If addons_folder set, addons are included
+/// Export the app's config from data_dir and the database from store_folder to save_path. If addons_folder set, addons are included
 pub fn export_app(
-    app: AppHandle,
+    events: &dyn EventSink,
+    save_path: PathBuf,
+    data_dir: PathBuf,
+    store_folder: PathBuf,
     app_version: String,
     addons_folder: Option<PathBuf>,
 ) -> Result<PathBuf, String> {
@@ -21,18 +34,13 @@ pub fn export_app(
         addons_folder.is_some()
     );
     let now = Instant::now();
-    let save_path = prompt_save_location(&app)?;
     let mut file = File::create(&save_path).map_err(|e| format!("create file: {}", e))?;
     let mut zip = zip::ZipWriter::new(&mut file);
 
-    let data_dir = app
-        .path()
-        .app_local_data_dir()
-        .expect("could not find data dir");
     zip_file_path(
         &mut zip,
         "addon-manager.db",
-        PathBuf::from(data_dir.join("addon-manager.db")),
+        PathBuf::from(store_folder.join("addon-manager.db")),
         SimpleFileOptions::default(),
     )
     .map_err(|e| format!("zipping db: {}", e))?;
@@ -58,7 +66,7 @@ pub fn export_app(
         for file in files.into_iter() {
             let (file_name, path) = file;
             zip_file_path(&mut zip, &file_name, path, SimpleFileOptions::default().compression_method(CompressionMethod::Stored)).unwrap();
-            app.emit("export_progress", progress.clone()).unwrap();
+            events.emit("export_progress", progress.clone());
             progress.value += 1;
         }
     }
@@ -70,7 +78,8 @@ pub fn export_app(
     Ok(save_path)
 }
 
-fn prompt_save_location(app: &AppHandle) -> Result<PathBuf, String> {
+#[cfg(feature = "gui")]
+pub fn prompt_save_location(app: &AppHandle) -> Result<PathBuf, String> {
     app.dialog()
         .file()
         .set_file_name("addon-manager-export.zip")
@@ -82,6 +91,17 @@ fn prompt_save_location(app: &AppHandle) -> Result<PathBuf, String> {
         .map_err(|e| e.to_string())
 }
 
+#[cfg(feature = "gui")]
+pub fn prompt_fastdl_location(app: &AppHandle) -> Result<PathBuf, String> {
+    app.dialog()
+        .file()
+        .set_title("Choose FastDL Folder")
+        .blocking_pick_folder()
+        .ok_or(String::from("failed to pick folder"))?
+        .into_path()
+        .map_err(|e| e.to_string())
+}
+
 fn zip_file_path<T>(
     zip: &mut ZipWriter<T>,
     file_name: &str,
@@ -179,3 +199,281 @@ fn export_get_addon_files(
     Ok(files)
 }
 
+
+/// Folders of the content clients need to download to play a map, anything else is server side
+const FASTDL_FOLDERS: [&str; 5] = ["materials", "models", "sound", "particles", "resource"];
+const FASTDL_MANIFEST: &str = "manifest.json";
+
+/// Whether a file in an addon is content a client downloads, maps/*.bsp and custom assets
+fn is_fastdl_file(path: &str) -> bool {
+    let path = path.to_lowercase();
+    if let Some(map) = path.strip_prefix("maps/") {
+        return !map.contains('/') && map.ends_with(".bsp");
+    }
+    FASTDL_FOLDERS
+        .iter()
+        .any(|folder| path.strip_prefix(folder).is_some_and(|rest| rest.starts_with('/')))
+}
+
+#[derive(Clone, Default)]
+/// Signals of the running fastdl export, so it can be cancelled
+pub struct FastDlSignal {
+    running: Arc<AtomicBool>,
+    cancelled: Arc<AtomicBool>,
+}
+impl FastDlSignal {
+    /// Marks an export as running, returns false if one already is
+    pub fn start(&self) -> bool {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        self.cancelled.store(false, Ordering::SeqCst);
+        true
+    }
+    pub fn finish(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FastDlFile {
+    /// Path relative to game folder, without .bz2
+    pub path: String,
+    pub size: u64,
+    pub compressed_size: u64,
+    pub crc: u32,
+    /// Filename of the addon file came from
+    pub addon: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+/// Written to manifest.json in the root of the fastdl folder
+pub struct FastDlManifest {
+    /// Unix seconds
+    pub generated_at: i64,
+    pub files: Vec<FastDlFile>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FastDlSummary {
+    /// Files in manifest
+    pub files: u32,
+    /// Files compressed this run
+    pub written: u32,
+    /// Files unchanged since the last export into the same folder
+    pub unchanged: u32,
+    /// Total uncompressed size of files in manifest
+    pub size: u64,
+    pub compressed_size: u64,
+    /// Files other addons already provided, which were left out
+    pub conflicts: Vec<String>,
+    /// Addons or files that could not be read and were left out, as "filename: error"
+    pub skipped: Vec<String>,
+    /// Files of the previous export no longer in the manifest, which were deleted
+    pub removed: u32,
+    pub cancelled: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// Which addons to export for fastdl
+pub enum FastDlSource {
+    /// Items of a profile
+    Profile { id: i64 },
+    /// Managed addons, by id (file hash)
+    Addons { ids: Vec<String> },
+}
+
+/// Returns paths of the addon files of source, skipping any that are missing
+pub async fn get_fastdl_addons(
+    addons: &AddonStorage,
+    addons_folder: &PathBuf,
+    source: FastDlSource,
+) -> Result<Vec<PathBuf>, String> {
+    let (hashes, workshop_ids) = match source {
+        FastDlSource::Profile { id } => {
+            let items = addons.get_profile_items(id).await.map_err(|e| e.to_string())?;
+            (items.ids, items.workshop_ids)
+        }
+        FastDlSource::Addons { ids } => (ids, Vec::new()),
+    };
+    let mut paths = Vec::with_capacity(hashes.len() + workshop_ids.len());
+    for id in hashes {
+        let hash = FileHash::from_str(&id).map_err(|e| format!("bad id {}: {}", id, e))?;
+        match addons.get_filename(&hash).await.map_err(|e| e.to_string())? {
+            Some(filename) => paths.push(addons_folder.join(filename)),
+            None => warn!("fastdl: addon {} is missing, skipping", id),
+        }
+    }
+    for id in workshop_ids {
+        let path = addons_folder.join("workshop").join(format!("{}.vpk", id));
+        match path.is_file() {
+            true => paths.push(path),
+            false => warn!("fastdl: workshop item {} is missing, skipping", id),
+        }
+    }
+    Ok(paths)
+}
+
+/// Compresses data into path.bz2, through a temporary file so a cancelled export leaves no partial files
+fn write_bz2(path: &PathBuf, data: &[u8]) -> Result<u64, String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("create {}: {}", parent.display(), e))?;
+    }
+    let tmp_path = path.with_extension("bz2.tmp");
+    let file = File::create(&tmp_path).map_err(|e| format!("create {}: {}", tmp_path.display(), e))?;
+    let mut encoder = BzEncoder::new(file, bzip2::Compression::best());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .and_then(|file| file.sync_all())
+        .map_err(|e| format!("compress {}: {}", path.display(), e))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| format!("write {}: {}", path.display(), e))?;
+    Ok(path.metadata().map(|meta| meta.len()).unwrap_or_default())
+}
+
+/// Writes a fastdl tree into dest, with a bzip2 compressed copy (.bz2) of each map and custom asset in addons,
+/// and a manifest of them. Files listed in the previous manifest with the same crc are not compressed again, and ones
+/// no longer exported are deleted. Addons earlier in the list take priority when more than one has a file, and
+/// addons that cannot be read are skipped
+pub fn export_fastdl(
+    events: &dyn EventSink,
+    signal: &FastDlSignal,
+    addons: Vec<PathBuf>,
+    dest: PathBuf,
+) -> Result<FastDlSummary, String> {
+    info!("Starting fastdl export of {} addons to {}", addons.len(), dest.display());
+    let now = Instant::now();
+    std::fs::create_dir_all(&dest).map_err(|e| format!("create {}: {}", dest.display(), e))?;
+    let manifest_path = dest.join(FASTDL_MANIFEST);
+    let previous: HashMap<String, FastDlFile> = std::fs::read_to_string(&manifest_path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<FastDlManifest>(&contents).ok())
+        .map(|manifest| manifest.files.into_iter().map(|file| (file.path.to_lowercase(), file)).collect())
+        .unwrap_or_default();
+
+    // Find all files first for progress
+    let mut skipped: Vec<String> = Vec::new();
+    let mut vpks: Vec<(String, VpkDirectory)> = Vec::with_capacity(addons.len());
+    for path in addons {
+        let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        match VpkDirectory::open(&path) {
+            Ok(vpk) => vpks.push((filename, vpk)),
+            Err(e) => {
+                warn!("fastdl: skipping {}: {}", filename, e);
+                skipped.push(format!("{}: {}", filename, e));
+            }
+        }
+    }
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut conflicts: Vec<String> = Vec::new();
+    let mut files: Vec<(&str, &VpkDirectory, &VpkFile)> = Vec::new();
+    for (filename, vpk) in &vpks {
+        for file in vpk.files.iter().filter(|file| is_fastdl_file(&file.path)) {
+            if !is_safe_path(&file.path) {
+                warn!("{}: skipping unsafe path {}", filename, file.path);
+            } else if seen.insert(file.path.to_lowercase()) {
+                files.push((filename.as_str(), vpk, file));
+            } else {
+                conflicts.push(file.path.clone());
+            }
+        }
+    }
+
+    let mut manifest = FastDlManifest {
+        generated_at: chrono::Utc::now().timestamp(),
+        files: Vec::with_capacity(files.len()),
+    };
+    let mut progress = ProgressPayload::new(0, files.len() as u32);
+    let (mut written, mut unchanged) = (0, 0);
+    let mut cancelled = false;
+    // Paths of previous manifest gone through, the rest are kept as they were on cancel
+    let mut visited: HashSet<String> = HashSet::with_capacity(files.len());
+    for (filename, vpk, file) in files {
+        if signal.is_cancelled() {
+            info!("fastdl export cancelled");
+            cancelled = true;
+            break;
+        }
+        let out_path = dest.join(format!("{}.bz2", file.path));
+        visited.insert(file.path.to_lowercase());
+        let compressed_size = match previous.get(&file.path.to_lowercase()) {
+            Some(prev) if prev.crc == file.crc && prev.size == file.size && out_path.is_file() => {
+                unchanged += 1;
+                Ok(prev.compressed_size)
+            }
+            _ => vpk
+                .read(file)
+                .and_then(|data| write_bz2(&out_path, &data))
+                .inspect(|_| written += 1),
+        };
+        let compressed_size = match compressed_size {
+            Ok(size) => size,
+            Err(e) => {
+                warn!("fastdl: skipping {} of {}: {}", file.path, filename, e);
+                skipped.push(format!("{}: {}", filename, e));
+                progress.value += 1;
+                events.emit("fastdl_progress", progress.clone());
+                continue;
+            }
+        };
+        manifest.files.push(FastDlFile {
+            path: file.path.clone(),
+            size: file.size,
+            compressed_size,
+            crc: file.crc,
+            addon: filename.to_string(),
+        });
+        progress.value += 1;
+        events.emit("fastdl_progress", progress.clone());
+    }
+
+    // A cancelled export has not gone through every file, so its previous files are kept, and stay in the manifest
+    // to be skipped or removed by the next export
+    let mut removed = 0;
+    if cancelled {
+        manifest.files.extend(
+            previous
+                .iter()
+                .filter(|(path, _)| !visited.contains(*path))
+                .map(|(_, file)| file.clone()),
+        );
+    } else {
+        let exported: HashSet<String> = manifest.files.iter().map(|file| file.path.to_lowercase()).collect();
+        for (path, file) in &previous {
+            if exported.contains(path) || !is_safe_path(&file.path) {
+                continue;
+            }
+            let out_path = dest.join(format!("{}.bz2", file.path));
+            if std::fs::remove_file(&out_path).is_ok() {
+                removed += 1;
+            }
+        }
+    }
+
+    // Manifest is written on cancel too, so the next export can skip what was done
+    let contents = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    std::fs::write(&manifest_path, contents).map_err(|e| format!("write manifest: {}", e))?;
+    let summary = FastDlSummary {
+        files: manifest.files.len() as u32,
+        written,
+        unchanged,
+        size: manifest.files.iter().map(|file| file.size).sum(),
+        compressed_size: manifest.files.iter().map(|file| file.compressed_size).sum(),
+        conflicts,
+        skipped,
+        removed,
+        cancelled,
+    };
+    info!(
+        "Fastdl export complete. {} files ({} written, {} unchanged, {} removed, {} skipped). Time elapsed: {}",
+        summary.files, written, unchanged, removed, summary.skipped.len(), now.elapsed().as_secs()
+    );
+    Ok(summary)
+}
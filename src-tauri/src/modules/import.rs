@@ -0,0 +1,202 @@
+use crate::modules::cfg::{AppConfig, ConfigImportMode};
+use crate::util::defs::ProgressPayload;
+use crate::util::events::EventSink;
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Instant;
+#[cfg(feature = "gui")]
+use tauri::AppHandle;
+#[cfg(feature = "gui")]
+use tauri_plugin_dialog::DialogExt;
+use zip::ZipArchive;
+
+/// Files every export contains, see export_app
+const REQUIRED_FILES: [&str; 3] = ["addon-manager.db", "config.json", "version.txt"];
+
+#[derive(Serialize, Debug)]
+/// Contents of an export, shown to user before importing
+pub struct ImportSummary {
+    pub path: PathBuf,
+    /// App version the export was made with
+    pub version: String,
+    /// Number of addon files included in export
+    pub addon_files: u32,
+    /// Total size in bytes of included addon files
+    pub addon_files_size: u64,
+    /// Included addon files that already exist in the addons folder, these are skipped
+    pub existing_addon_files: u32,
+    /// The export's addons folder, if it differs from the local one. Local one is kept
+    pub other_addons_folder: Option<PathBuf>,
+}
+
+#[cfg(feature = "gui")]
+pub fn prompt_import_location(app: &AppHandle) -> Result<PathBuf, String> {
+    app.dialog()
+        .file()
+        .set_title("Choose Export to Import")
+        .add_filter("ZIP Archive", &["zip"])
+        .blocking_pick_file()
+        .ok_or(String::from("failed to pick file"))?
+        .into_path()
+        .map_err(|e| e.to_string())
+}
+
+fn open_archive(path: &PathBuf) -> Result<ZipArchive<File>, String> {
+    let file = File::open(path).map_err(|e| format!("open file: {}", e))?;
+    ZipArchive::new(file).map_err(|e| format!("not a valid export: {}", e))
+}
+
+/// Returns file name inside the export's addons folder, exports made on windows use \ as separator
+fn get_addon_file_name(entry_name: &str) -> Option<&str> {
+    let name = entry_name
+        .strip_prefix("addons/")
+        .or_else(|| entry_name.strip_prefix("addons\\"))?;
+    // Only files directly in the folder are exported
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return None;
+    }
+    Some(name)
+}
+
+/// Parses "major.minor.patch", ignoring any suffix like "-beta"
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version
+        .trim()
+        .split(['.', '-', '+'])
+        .map(|part| part.parse::<u32>());
+    Some((parts.next()?.ok()?, parts.next()?.ok()?, parts.next()?.ok()?))
+}
+
+/// An export is compatible if it has the same major version and is not newer than current app,
+/// as a newer database may have migrations this version does not know about
+fn is_compatible_version(export_version: &str, app_version: &str) -> bool {
+    match (parse_version(export_version), parse_version(app_version)) {
+        (Some(export), Some(app)) => export.0 == app.0 && export <= app,
+        _ => false,
+    }
+}
+
+/// Checks the export has all required files and a compatible version, and summarizes its contents
+pub fn read_import_summary(
+    path: PathBuf,
+    app_version: &str,
+    addons_folder: Option<&PathBuf>,
+) -> Result<ImportSummary, String> {
+    let mut zip = open_archive(&path)?;
+    for name in REQUIRED_FILES {
+        zip.by_name(name)
+            .map_err(|_| format!("not a valid export: missing {}", name))?;
+    }
+
+    let mut version = String::new();
+    zip.by_name("version.txt")
+        .and_then(|mut file| Ok(file.read_to_string(&mut version)?))
+        .map_err(|e| format!("read version: {}", e))?;
+    let version = version.trim().to_string();
+    if !is_compatible_version(&version, app_version) {
+        return Err(format!(
+            "Export was made with version {}, which is not compatible with this version ({})",
+            version, app_version
+        ));
+    }
+
+    let config: AppConfig = zip
+        .by_name("config.json")
+        .map_err(|e| e.to_string())
+        .and_then(|file| serde_json::from_reader(file).map_err(|e| e.to_string()))
+        .map_err(|e| format!("read config: {}", e))?;
+    let other_addons_folder = config
+        .addons_folder
+        .filter(|folder| Some(folder) != addons_folder);
+
+    let mut addon_files = 0;
+    let mut addon_files_size = 0;
+    let mut existing_addon_files = 0;
+    for i in 0..zip.len() {
+        let file = zip.by_index(i).map_err(|e| format!("read export: {}", e))?;
+        let Some(name) = get_addon_file_name(file.name()) else { continue };
+        addon_files += 1;
+        addon_files_size += file.size();
+        if addons_folder.is_some_and(|folder| folder.join(name).exists()) {
+            existing_addon_files += 1;
+        }
+    }
+
+    Ok(ImportSummary {
+        path,
+        version,
+        addon_files,
+        addon_files_size,
+        existing_addon_files,
+        other_addons_folder,
+    })
+}
+
+/// Extracts the export's database to the given path
+pub fn extract_db(path: &PathBuf, dest: &PathBuf) -> Result<(), String> {
+    let mut zip = open_archive(path)?;
+    let mut file = zip
+        .by_name("addon-manager.db")
+        .map_err(|e| format!("read db: {}", e))?;
+    let mut out = File::create(dest).map_err(|e| format!("create db: {}", e))?;
+    std::io::copy(&mut file, &mut out).map_err(|e| format!("extract db: {}", e))?;
+    Ok(())
+}
+
+/// Applies the export's config onto the current config
+pub fn import_config(path: &PathBuf, config: &mut AppConfig, mode: ConfigImportMode) -> Result<(), String> {
+    let mut zip = open_archive(path)?;
+    let file = zip
+        .by_name("config.json")
+        .map_err(|e| format!("read config: {}", e))?;
+    let imported: AppConfig =
+        serde_json::from_reader(file).map_err(|e| format!("parse config: {}", e))?;
+    config.import(imported, mode);
+    Ok(())
+}
+
+/// Extracts the export's addons into addons folder, skipping any that already exist.
+/// Returns number of files extracted
+pub fn extract_addons(events: &dyn EventSink, path: &PathBuf, addons_folder: &PathBuf) -> Result<u32, String> {
+    let now = Instant::now();
+    let mut zip = open_archive(path)?;
+    let indexes: Vec<usize> = (0..zip.len())
+        .filter(|i| {
+            zip.by_index(*i)
+                .is_ok_and(|file| get_addon_file_name(file.name()).is_some())
+        })
+        .collect();
+    let mut progress = ProgressPayload::new(0, indexes.len() as u32);
+    let mut extracted = 0;
+    for i in indexes {
+        let mut file = zip.by_index(i).map_err(|e| format!("read export: {}", e))?;
+        let name = get_addon_file_name(file.name()).unwrap().to_string();
+        let dest = addons_folder.join(&name);
+        if dest.exists() {
+            debug!("import: skipping existing {}", name);
+        } else {
+            // Write to temp file first, so an interrupted import doesn't leave a partial vpk
+            let tmp_dest = addons_folder.join(format!("{}.tmp", name));
+            let result = File::create(&tmp_dest)
+                .and_then(|mut out| std::io::copy(&mut file, &mut out))
+                .and_then(|_| std::fs::rename(&tmp_dest, &dest));
+            if let Err(e) = result {
+                warn!("import: failed to extract {}: {}", name, e);
+                std::fs::remove_file(&tmp_dest).ok();
+            } else {
+                extracted += 1;
+            }
+        }
+        progress.value += 1;
+        events.emit("import_progress", progress.clone());
+    }
+    info!(
+        "Extracted {} addons from import. Time elapsed: {}",
+        extracted,
+        now.elapsed().as_secs()
+    );
+    Ok(extracted)
+}
@@ -0,0 +1,216 @@
+use crate::modules::addons::ItemResult;
+use crate::modules::cfg::{AppConfig, Installation};
+use crate::modules::server::is_server_install;
+use crate::modules::store::{AddonStorage, AddonStorageContainer, FileHash};
+use crate::modules::thumbnails::ThumbnailCache;
+use crate::scan::worker::async_store_outside_scan;
+use log::{debug, info, warn};
+use serde::Serialize;
+use sqlx::__rt::spawn_blocking;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Installation whose database is in the data dir itself, as it was before installations existed
+pub const DEFAULT_INSTALLATION_ID: u32 = 1;
+
+/// Returns folder with the database and thumbnails of installation
+pub fn get_store_folder(data_dir: &PathBuf, id: u32) -> PathBuf {
+    match id {
+        DEFAULT_INSTALLATION_ID => data_dir.clone(),
+        id => data_dir.join("installations").join(id.to_string()),
+    }
+}
+
+/// Opens database of installation, running any migrations
+pub async fn open_installation_storage(data_dir: &PathBuf, id: u32) -> Result<AddonStorage, String> {
+    let addons = AddonStorage::new(get_store_folder(data_dir, id)).await?;
+    addons.run_migrations().await.map_err(|e| e.to_string())?;
+    Ok(addons)
+}
+
+/// Adds an installation by its addons folder, without making it active
+pub fn add_installation(cfg: &mut AppConfig, name: &str, addons_folder: PathBuf) -> Result<Installation, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Installation name cannot be empty".to_string());
+    }
+    if !addons_folder.is_dir() {
+        return Err("Addons folder must exist and be a directory".to_string());
+    }
+    if cfg.installations.iter().any(|install| install.addons_folder == addons_folder) {
+        return Err("Addons folder is already an installation".to_string());
+    }
+    let id = cfg.installations.iter().map(|install| install.id).max().unwrap_or(0) + 1;
+    let install = Installation {
+        id,
+        name: name.to_string(),
+        server_mode: is_server_install(&addons_folder),
+        addons_folder,
+    };
+    info!("Added installation {} \"{}\" ({})", id, install.name, install.addons_folder.display());
+    cfg.installations.push(install.clone());
+    Ok(install)
+}
+
+/// Removes installation and deletes its database. The active and default installation cannot be removed
+pub fn remove_installation(cfg: &mut AppConfig, data_dir: &PathBuf, id: u32) -> Result<(), String> {
+    if cfg.active_installation == Some(id) {
+        return Err("The active installation cannot be removed".to_string());
+    }
+    if id == DEFAULT_INSTALLATION_ID {
+        return Err("The default installation cannot be removed".to_string());
+    }
+    let index = cfg
+        .installations
+        .iter()
+        .position(|install| install.id == id)
+        .ok_or_else(|| format!("installation {} does not exist", id))?;
+    let install = cfg.installations.remove(index);
+    let store_folder = get_store_folder(data_dir, id);
+    if store_folder.exists() {
+        std::fs::remove_dir_all(&store_folder).map_err(|e| format!("failed to delete database: {}", e))?;
+    }
+    info!("Removed installation {} \"{}\"", id, install.name);
+    Ok(())
+}
+
+/// Makes installation active: its database takes the place of the current one in addons, and its thumbnails are used.
+/// Any scan or watcher of the previous installation should be stopped first
+pub async fn switch_installation(
+    cfg: &mut AppConfig,
+    data_dir: &PathBuf,
+    addons: &AddonStorageContainer,
+    thumbnails: &ThumbnailCache,
+    id: u32,
+) -> Result<(), String> {
+    if cfg.get_installation(id).is_none() {
+        return Err(format!("installation {} does not exist", id));
+    }
+    let storage = open_installation_storage(data_dir, id).await?;
+    {
+        let mut addons = addons.write().await;
+        addons.close().await;
+        *addons = storage;
+    }
+    thumbnails.set_store_folder(&get_store_folder(data_dir, id));
+    cfg.set_active_installation(id)?;
+    info!("Switched to installation {}", id);
+    Ok(())
+}
+
+/// Copies managed addons into another installation's addons folder by their hash, adding them to its database.
+/// Addons the other installation already has are skipped
+pub async fn copy_addons(
+    from: &AddonStorage,
+    from_folder: &PathBuf,
+    to: AddonStorageContainer,
+    to_folder: &PathBuf,
+    thumbnails: &ThumbnailCache,
+    ids: Vec<String>,
+) -> Vec<ItemResult> {
+    let mut results = Vec::with_capacity(ids.len());
+    for id in ids {
+        let hash = match FileHash::from_str(&id) {
+            Ok(hash) => hash,
+            Err(e) => {
+                results.push(ItemResult::error(id, format!("bad id: {}", e)));
+                continue;
+            }
+        };
+        match copy_addon(from, from_folder, &to, to_folder, thumbnails, &hash).await {
+            Ok(filename) => results.push(ItemResult::ok(filename)),
+            Err(e) => {
+                warn!("copy: {}", e);
+                results.push(ItemResult::error(id, e));
+            }
+        }
+    }
+    results
+}
+
+async fn copy_addon(
+    from: &AddonStorage,
+    from_folder: &PathBuf,
+    to: &AddonStorageContainer,
+    to_folder: &PathBuf,
+    thumbnails: &ThumbnailCache,
+    hash: &FileHash,
+) -> Result<String, String> {
+    let filename = from
+        .get_filename(hash)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "addon is missing".to_string())?;
+    if let Some(existing) = to.read().await.get_filename(hash).await.map_err(|e| e.to_string())? {
+        debug!("{} already in installation as {}, skipping", filename, existing);
+        return Ok(existing);
+    }
+    let dest = to_folder.join(&filename);
+    if dest.exists() {
+        return Err(format!("{}: a different file with the same name exists", filename));
+    }
+    let src = from_folder.join(&filename);
+    {
+        let dest = dest.clone();
+        spawn_blocking(move || std::fs::copy(&src, &dest))
+            .await
+            .map_err(|e| format!("{}: failed to copy: {}", filename, e))?;
+    }
+
+    async_store_outside_scan(dest, to, thumbnails, "copy")
+        .await
+        .map_err(|e| format!("{}: {}", filename, e))?;
+    Ok(filename)
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProfileCopy {
+    /// Id of the profile in the other installation
+    pub profile_id: i64,
+    pub name: String,
+    /// Addons of the profile the other installation does not have, by id (hash)
+    pub missing: Vec<String>,
+}
+
+/// Copies a profile into another installation's database. Addons stay referenced by hash,
+/// so any that are missing there can be copied over with copy_addons
+pub async fn copy_profile(from: &AddonStorage, to: &AddonStorage, id: i64) -> Result<ProfileCopy, String> {
+    let profile = from
+        .get_profile(id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("profile {} does not exist", id))?;
+    let items = from.get_profile_items(id).await.map_err(|e| e.to_string())?;
+    let hashes: Vec<FileHash> = items
+        .ids
+        .iter()
+        .map(|id| FileHash::from_str(id).map_err(|e| format!("bad id {}: {}", id, e)))
+        .collect::<Result<_, _>>()?;
+
+    // Profile names are unique, number the copy if the name is taken
+    let names: HashSet<String> = to
+        .list_profiles()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|profile| profile.name)
+        .collect();
+    let mut name = profile.name.clone();
+    let mut i = 2;
+    while names.contains(&name) {
+        name = format!("{} ({})", profile.name, i);
+        i += 1;
+    }
+
+    let mut missing = Vec::new();
+    for hash in &hashes {
+        if to.get_filename(hash).await.map_err(|e| e.to_string())?.is_none() {
+            missing.push(hash.to_string());
+        }
+    }
+    let profile_id = to
+        .create_profile(&name, &hashes, &items.workshop_ids)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(ProfileCopy { profile_id, name, missing })
+}
@@ -2,13 +2,16 @@ use std::path::PathBuf;
 use std::time::Duration;
 use log::{debug, error, info, trace};
 use steam_workshop_api::SteamWorkshop;
-use tauri::{AppHandle, Manager, State};
-use crate::commands::addons::ItemResult;
-use crate::modules::cfg::{AppConfig, AppConfigContainer};
+use crate::modules::addons::ItemResult;
+use crate::modules::cfg::AppConfig;
 
-pub fn migrate_workshop(app: AppHandle, ids: Vec<i64>) -> Result<Vec<ItemResult>, String> {
-    let cfg = app.state::<AppConfigContainer>();
-    let cfg = cfg.blocking_lock();
+const SERVER_MODE_ERROR: &str = "Dedicated servers have no workshop subscriptions to migrate";
+
+/// Copies workshop folder items into the addons folder, unsubscribing from them if an api key is set
+pub fn migrate_workshop(cfg: &AppConfig, ids: Vec<i64>) -> Result<Vec<ItemResult>, String> {
+    if cfg.server_mode {
+        return Err(SERVER_MODE_ERROR.to_string());
+    }
     let addons_folder = cfg
         .addons_folder
         .as_ref()
@@ -16,7 +19,6 @@ pub fn migrate_workshop(app: AppHandle, ids: Vec<i64>) -> Result<Vec<ItemResult>
         .to_owned();
     let workshop_folder = addons_folder.join("workshop");
     let (steam, can_unsubscribe) = cfg.steam();
-    drop(cfg);
     let mut i = 0;
     debug!(
         "ws={:?} addons={:?} can_unsubscribe={} ids={:?}",
@@ -54,12 +56,11 @@ pub fn migrate_workshop(app: AppHandle, ids: Vec<i64>) -> Result<Vec<ItemResult>
     Ok(results)
 }
 
-pub fn unsubscribe_workshop(app: AppHandle, ids: Vec<i64>) -> Result<Vec<ItemResult>, String> {
-    let cfg = app.state::<AppConfigContainer>();
-    let (steam, can_unsubscribe) = {
-        let cfg = cfg.blocking_lock();
-        cfg.steam()
-    };
+pub fn unsubscribe_workshop(cfg: &AppConfig, ids: Vec<i64>) -> Result<Vec<ItemResult>, String> {
+    if cfg.server_mode {
+        return Err(SERVER_MODE_ERROR.to_string());
+    }
+    let (steam, can_unsubscribe) = cfg.steam();
     if !can_unsubscribe {
         return Err("Can only unsubscribe if your own steam api key is provided".to_string());
     }
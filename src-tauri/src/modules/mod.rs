@@ -1,6 +1,18 @@
 pub mod addons;
 pub mod export;
+pub mod import;
+pub mod sort;
 pub mod store;
+pub mod thumbnails;
+pub mod vpk;
+pub mod vtf;
 
 pub mod cfg;
+pub mod collections;
+pub mod conflicts;
+pub mod duplicates;
+pub mod installations;
 pub mod migrate;
+pub mod profiles;
+pub mod server;
+pub mod updates;
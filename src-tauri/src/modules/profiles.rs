@@ -0,0 +1,107 @@
+use crate::modules::addons::ItemResult;
+use crate::models::profile::ProfileItems;
+use crate::modules::store::{AddonStorage, FileHash};
+use crate::scan::helpers::get_workshop_folder_ws_ids;
+use crate::util::edit_addon_list;
+use l4d2_addon_parser::addon_list::AddonList;
+use log::{info, warn};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Name of a workshop folder item, as it appears in addonlist.txt
+pub fn workshop_list_name(id: i64) -> String {
+    format!("workshop\\{}.vpk", id)
+}
+
+/// Returns the addons currently enabled in addonlist.txt, for creating a profile from current state
+pub async fn get_enabled_items(addons: &AddonStorage, addons_folder: &PathBuf) -> Result<ProfileItems, String> {
+    let list = AddonList::new(&addons_folder.parent().unwrap().join("addonlist.txt"))
+        .map_err(|e| format!("failed to read addonlist.txt: {}", e))?;
+    let ids = addons
+        .list_hash_filenames()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|(_, filename)| filename.as_ref().is_some_and(|f| list.is_enabled(f)))
+        .map(|(hash, _)| hash.to_string())
+        .collect();
+    let workshop_ids = get_workshop_folder_ws_ids(addons_folder)
+        .into_iter()
+        .filter(|id| list.is_enabled(&workshop_list_name(*id)))
+        .collect();
+    Ok(ProfileItems { ids, workshop_ids })
+}
+
+/// Enables every addon in profile and disables all other known addons, saving addonlist.txt once.
+/// Returns a result for each item in profile, with an error for items no longer present
+pub async fn apply_profile(
+    addons: &AddonStorage,
+    addons_folder: &PathBuf,
+    id: i64,
+) -> Result<Vec<ItemResult>, String> {
+    let profile = addons
+        .get_profile(id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("profile {} does not exist", id))?;
+    let items = addons.get_profile_items(id).await.map_err(|e| e.to_string())?;
+    let managed = addons.list_hash_filenames().await.map_err(|e| e.to_string())?;
+    let workshop_folder_ids: HashSet<i64> = get_workshop_folder_ws_ids(addons_folder).into_iter().collect();
+
+    let profile_hashes: HashSet<FileHash> = items
+        .ids
+        .iter()
+        .filter_map(|id| FileHash::from_str(id).ok())
+        .collect();
+    let profile_workshop_ids: HashSet<i64> = items.workshop_ids.iter().copied().collect();
+
+    let addonlist_path = addons_folder.parent().unwrap().join("addonlist.txt");
+    let results = edit_addon_list(&addonlist_path, |list| {
+        let mut results: Vec<ItemResult> = Vec::new();
+        let mut found_hashes: HashSet<&FileHash> = HashSet::new();
+        for (hash, filename) in &managed {
+            let Some(filename) = filename else { continue };
+            let enabled = profile_hashes.contains(hash);
+            if enabled {
+                found_hashes.insert(hash);
+            }
+            match list.set_enabled(filename.to_string(), enabled) {
+                Ok(()) if enabled => results.push(ItemResult::ok(filename.to_string())),
+                Ok(()) => {}
+                Err(e) => results.push(ItemResult::error(filename.to_string(), e.to_string())),
+            }
+        }
+        for ws_id in &workshop_folder_ids {
+            let name = workshop_list_name(*ws_id);
+            let enabled = profile_workshop_ids.contains(ws_id);
+            match list.set_enabled(name.clone(), enabled) {
+                Ok(()) if enabled => results.push(ItemResult::ok(name)),
+                Ok(()) => {}
+                Err(e) => results.push(ItemResult::error(name, e.to_string())),
+            }
+        }
+
+        // Report anything the profile references that we could not enable
+        for id in &items.ids {
+            let found = FileHash::from_str(id).is_ok_and(|hash| found_hashes.contains(&hash));
+            if !found {
+                results.push(ItemResult::error(id.to_string(), "Addon is no longer present".to_string()));
+            }
+        }
+        for ws_id in profile_workshop_ids.iter().filter(|id| !workshop_folder_ids.contains(id)) {
+            results.push(ItemResult::error(
+                workshop_list_name(*ws_id),
+                "Workshop item is no longer in workshop folder".to_string(),
+            ));
+        }
+        results
+    })?;
+
+    let missing = results.iter().filter(|r| matches!(r, ItemResult::Error { .. })).count();
+    if missing > 0 {
+        warn!("Applied profile \"{}\" with {} errors", profile.name, missing);
+    } else {
+        info!("Applied profile \"{}\"", profile.name);
+    }
+    Ok(results)
+}
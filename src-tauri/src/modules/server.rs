@@ -0,0 +1,81 @@
+use crate::modules::cfg::AppConfig;
+use crate::modules::store::AddonStorage;
+use l4d2_addon_parser::addon_list::AddonList;
+use log::info;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Executables found in the root of a dedicated server install
+const SERVER_EXECUTABLES: [&str; 3] = ["srcds_run", "srcds_linux", "srcds.exe"];
+/// Name of the generated config, in left4dead2/cfg
+const SERVER_CFG_NAME: &str = "addon_manager.cfg";
+
+/// Returns root of the install, which has the game executables, from the addons folder (root/left4dead2/addons)
+pub fn get_install_root(addons_folder: &PathBuf) -> Option<PathBuf> {
+    addons_folder.ancestors().nth(2).map(|path| path.to_path_buf())
+}
+
+/// Checks if addons folder is in a dedicated server (srcds) install instead of the game
+pub fn is_server_install(addons_folder: &PathBuf) -> bool {
+    get_install_root(addons_folder)
+        .is_some_and(|root| SERVER_EXECUTABLES.iter().any(|exe| root.join(exe).is_file()))
+}
+
+#[derive(Serialize, Debug)]
+pub struct ServerConfigOutput {
+    /// Where the config was written to, to be exec'd from server.cfg
+    pub path: PathBuf,
+    pub contents: String,
+    /// Workshop ids of enabled addons, to add to the hosted collection
+    pub workshop_ids: Vec<i64>,
+}
+
+/// Generates a config with the workshop and download cvars for the server's addons,
+/// and writes it to left4dead2/cfg/addon_manager.cfg
+pub async fn generate_server_config(addons: &AddonStorage, cfg: &AppConfig) -> Result<ServerConfigOutput, String> {
+    let addons_folder = cfg.addons_folder.as_ref().ok_or("addons folder missing".to_string())?;
+    let game_folder = addons_folder.parent().unwrap();
+    let list = AddonList::new(&game_folder.join("addonlist.txt"))
+        .map_err(|e| format!("failed to read addonlist.txt: {}", e))?;
+    let mut workshop_ids: Vec<i64> = addons
+        .list_filenames_workshop_ids()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|(filename, _)| list.is_enabled(filename))
+        .filter_map(|(_, workshop_id)| workshop_id)
+        .collect();
+    workshop_ids.sort_unstable();
+    workshop_ids.dedup();
+
+    let mut lines = vec![
+        "// Generated by L4D2 Addon Manager, changes will be overwritten".to_string(),
+        format!("// Add \"exec {}\" to server.cfg", SERVER_CFG_NAME.trim_end_matches(".cfg")),
+    ];
+    match cfg.server_collection_id {
+        Some(id) => lines.push(format!("host_workshop_collection \"{}\"", id)),
+        None => lines.push("// host_workshop_collection: no collection set".to_string()),
+    }
+    match &cfg.server_download_url {
+        Some(url) => {
+            lines.push("sv_allowdownload \"1\"".to_string());
+            lines.push(format!("sv_downloadurl \"{}\"", url));
+        }
+        None => lines.push("// sv_downloadurl: no download url set".to_string()),
+    }
+    if !workshop_ids.is_empty() {
+        lines.push(String::new());
+        lines.push("// Workshop items of enabled addons, for the hosted collection:".to_string());
+        for id in &workshop_ids {
+            lines.push(format!("// https://steamcommunity.com/sharedfiles/filedetails/?id={}", id));
+        }
+    }
+    let contents = lines.join("\n") + "\n";
+
+    let cfg_folder = game_folder.join("cfg");
+    std::fs::create_dir_all(&cfg_folder).map_err(|e| format!("failed to create cfg folder: {}", e))?;
+    let path = cfg_folder.join(SERVER_CFG_NAME);
+    std::fs::write(&path, &contents).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    info!("Wrote server config to {}", path.display());
+    Ok(ServerConfigOutput { path, contents, workshop_ids })
+}
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Max number of sort keys accepted in one sort
+const MAX_SORT_KEYS: usize = 5;
+
+#[derive(Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+/// Where NULL values are placed. Default uses sqlite's ordering (NULLs first when ascending)
+pub enum NullsOrder {
+    #[default]
+    Default,
+    First,
+    Last,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SortKey {
+    /// Name of the field to sort by, must be one of the sortable fields of the table
+    pub field: String,
+    #[serde(default)]
+    pub descending: bool,
+    #[serde(default)]
+    pub nulls: NullsOrder,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+/// Sort sent by frontend, either a single key or a list of keys where later keys break ties
+pub enum SelectedSort {
+    Single(SortKey),
+    Multi(Vec<SortKey>),
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+/// Table being sorted, each has its own set of sortable fields
+pub enum SortTable {
+    Addons,
+    Workshop,
+}
+
+#[derive(Debug, Serialize)]
+pub enum SortError {
+    UnknownField {
+        field: String,
+        table: SortTable,
+        /// Fields that can be sorted by for the table
+        allowed: Vec<&'static str>,
+    },
+    TooManyKeys {
+        count: usize,
+        max: usize,
+    },
+}
+impl Display for SortError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortError::UnknownField { field, allowed, .. } => {
+                write!(f, "Cannot sort by \"{}\", must be one of: {}", field, allowed.join(", "))
+            }
+            SortError::TooManyKeys { count, max } => {
+                write!(f, "Cannot sort by {} fields, at most {} can be used", count, max)
+            }
+        }
+    }
+}
+
+impl SortTable {
+    /// Returns (field name, sql column) of every sortable field
+    fn columns(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            SortTable::Addons => &[
+                ("title", "addons.title"),
+                ("filename", "addons.filename"),
+                ("file_size", "addons.file_size"),
+                ("updated_at", "addons.updated_at"),
+                ("created_at", "addons.created_at"),
+                ("author", "addons.author"),
+                ("version", "addons.version"),
+                ("flags", "addons.flags"),
+                ("workshop_id", "addons.workshop_id"),
+            ],
+            SortTable::Workshop => &[
+                ("title", "workshop_items.title"),
+                ("file_size", "workshop_items.file_size"),
+                ("time_updated", "workshop_items.time_updated"),
+                ("time_created", "workshop_items.time_created"),
+                // aliases so the same columns as addons can be used
+                ("updated_at", "workshop_items.time_updated"),
+                ("created_at", "workshop_items.time_created"),
+                ("author", "workshop_items.creator_id"),
+                ("flags", "workshop_items.flags"),
+                ("publishedfileid", "workshop_items.publishedfileid"),
+                ("workshop_id", "workshop_items.publishedfileid"),
+            ],
+        }
+    }
+
+    /// Primary key of the table, sorted by last so rows that tie on every key keep the same order between pages
+    fn primary_key(&self) -> &'static str {
+        match self {
+            SortTable::Addons => "addons.file_hash",
+            SortTable::Workshop => "workshop_items.publishedfileid",
+        }
+    }
+
+    fn column(&self, field: &str) -> Result<&'static str, SortError> {
+        self.columns()
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| SortError::UnknownField {
+                field: field.to_string(),
+                table: *self,
+                allowed: self.columns().iter().map(|(name, _)| *name).collect(),
+            })
+    }
+}
+
+impl SelectedSort {
+    pub fn new(field: &str, descending: bool) -> Self {
+        SelectedSort::Single(SortKey {
+            field: field.to_string(),
+            descending,
+            nulls: NullsOrder::Default,
+        })
+    }
+
+    pub fn keys(&self) -> &[SortKey] {
+        match self {
+            SelectedSort::Single(key) => std::slice::from_ref(key),
+            SelectedSort::Multi(keys) => keys,
+        }
+    }
+
+    /// Returns the "order by" clause contents, only using known columns of table
+    pub fn get_sql(&self, table: SortTable) -> Result<String, SortError> {
+        let keys = self.keys();
+        if keys.len() > MAX_SORT_KEYS {
+            return Err(SortError::TooManyKeys { count: keys.len(), max: MAX_SORT_KEYS });
+        }
+        let mut parts: Vec<String> = Vec::with_capacity(keys.len() + 1);
+        for key in keys {
+            let column = table.column(&key.field)?;
+            let nulls = match key.nulls {
+                NullsOrder::Default => "",
+                NullsOrder::First => " NULLS FIRST",
+                NullsOrder::Last => " NULLS LAST",
+            };
+            parts.push(format!("{} {}{}", column, if key.descending { "DESC" } else { "ASC" }, nulls));
+        }
+        parts.push(format!("{} ASC", table.primary_key()));
+        Ok(parts.join(", "))
+    }
+}
@@ -1,20 +1,29 @@
-use crate::models::addon::{StandardAddonWithTags, WorkshopEntry};
+use crate::models::addon::{StandardAddonWithTags, WorkshopEntry, WorkshopEntryWithFile};
+use crate::models::profile::{Profile, ProfileItems};
+use crate::models::scan::{ScanCounter, ScanError, ScanRecord, ScanStatus};
+use crate::modules::sort::{SelectedSort, SortError, SortTable};
+use crate::modules::vpk::VpkFile;
 use bitflags::bitflags;
 use chrono::DateTime;
 use l4d2_addon_parser::AddonInfo;
 use l4d2_addon_parser::addon_list::AddonList;
 use log::{debug, info};
+use rand::random;
 use serde::{Deserialize, Serialize};
 use sqlx::types::chrono;
 use sqlx::types::chrono::Utc;
-use sqlx::{AssertSqlSafe, FromRow, Pool, QueryBuilder, Sqlite};
+use sqlx::{AssertSqlSafe, FromRow, Pool, QueryBuilder, Sqlite, SqliteConnection, Transaction};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::fs;
+use std::fs::Metadata;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use hex::FromHexError;
 use steam_workshop_api::WorkshopItem;
-use tauri::async_runtime::Mutex;
+use tokio::sync::RwLock;
+use crate::util::{get_file_mtime, get_file_size};
 
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 /// for standard addons
@@ -44,7 +53,7 @@ impl From<u32> for AddonFlags {
     }
 }
 
-#[derive(Debug, sqlx::Type, PartialEq)]
+#[derive(Debug, Clone, sqlx::Type, PartialEq, Eq, Hash)]
 #[sqlx(transparent)]
 pub struct FileHash(pub Vec<u8>);
 impl Display for FileHash {
@@ -59,11 +68,32 @@ impl FileHash {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Size and modification time of an addon file, used to detect files unchanged since last scan
+pub struct FileStamp {
+    pub file_size: i64,
+    /// Modification time in unix ms, None if the platform or db entry does not have it
+    pub file_mtime: Option<i64>,
+}
+impl FileStamp {
+    pub fn from_meta(meta: &Metadata) -> Self {
+        Self {
+            file_size: get_file_size(meta),
+            file_mtime: get_file_mtime(meta),
+        }
+    }
+
+    /// Is the file the same as the stored stamp. Always false if either has no mtime
+    pub fn matches(&self, other: &FileStamp) -> bool {
+        self.file_mtime.is_some() && self == other
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 /// Information about the addon. This is used by both standard entries and workshop entries
 pub struct AddonData {
-    /// Name of the file addon was found in
-    pub filename: String,
+    /// Name of the file addon was found in, None if file is missing
+    pub filename: Option<String>,
     /// When addon file was last updated
     pub updated_at: DateTime<Utc>,
     /// When addon file was created
@@ -102,37 +132,187 @@ pub struct AddonEntry {
     pub enabled: Option<bool>,
 }
 
-#[derive(Deserialize)]
-pub struct SelectedSort {
-    field: String,
-    descending: bool,
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+/// Error from listing entries, sent to frontend as is
+pub enum ListError {
+    InvalidSort {
+        message: String,
+        field: String,
+        allowed: Vec<&'static str>,
+    },
+    TooManySortKeys {
+        message: String,
+        max: usize,
+    },
+    Database {
+        message: String,
+    },
+}
+impl From<SortError> for ListError {
+    fn from(err: SortError) -> Self {
+        let message = err.to_string();
+        match err {
+            SortError::UnknownField { field, allowed, .. } => ListError::InvalidSort { message, field, allowed },
+            SortError::TooManyKeys { max, .. } => ListError::TooManySortKeys { message, max },
+        }
+    }
+}
+impl From<sqlx::Error> for ListError {
+    fn from(err: sqlx::Error) -> Self {
+        ListError::Database { message: err.to_string() }
+    }
+}
+impl Display for ListError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListError::InvalidSort { message, .. } | ListError::TooManySortKeys { message, .. } => f.write_str(message),
+            ListError::Database { message } => write!(f, "Database error: {}", message),
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Debug)]
+/// Filters for listing addons. All set filters must match
+pub struct AddonFilter {
+    /// Full text search over title, author, tagline, description, workshop tags and user tags
+    pub query: Option<String>,
+    /// AddonFlags bits that must all be set
+    pub flags: Option<u32>,
+    /// Only entries enabled / disabled in addonlist.txt
+    pub enabled: Option<bool>,
+    /// Only entries with / without a missing file. Not used for workshop items
+    pub missing: Option<bool>,
+    /// Only entries with / without a linked workshop item. Not used for workshop items
+    pub workshop_linked: Option<bool>,
+    /// Minimum file size in bytes
+    pub min_size: Option<i64>,
+    /// Maximum file size in bytes
+    pub max_size: Option<i64>,
+    pub offset: Option<u32>,
+    pub limit: Option<u32>,
 }
-impl SelectedSort {
-    pub fn new(field: &str, descending: bool) -> Self {
-        Self { field: field.to_string(), descending }
+impl AddonFilter {
+    /// Turns user input into a FTS5 query, matching each word as a prefix.
+    /// Quotes are stripped so input can't produce a bad query
+    fn fts_query(&self) -> Option<String> {
+        let terms: Vec<String> = self.query.as_ref()?
+            .split_whitespace()
+            .map(|term| term.replace('"', ""))
+            .filter(|term| !term.is_empty())
+            .map(|term| format!("\"{}\"*", term))
+            .collect();
+        if terms.is_empty() {
+            return None;
+        }
+        Some(terms.join(" "))
+    }
+
+    /// Pushes " AND ..." conditions for a query on addons, joined with workshop_items as wi
+    fn push_addons_where(&self, query: &mut QueryBuilder<Sqlite>) {
+        if let Some(fts) = self.fts_query() {
+            query.push(" AND addons.file_hash IN (SELECT file_hash FROM addons_search WHERE addons_search MATCH ")
+                .push_bind(fts)
+                .push(")");
+        }
+        if let Some(missing) = self.missing {
+            query.push(if missing { " AND addons.filename IS NULL" } else { " AND addons.filename IS NOT NULL" });
+        }
+        if let Some(linked) = self.workshop_linked {
+            query.push(if linked { " AND wi.publishedfileid IS NOT NULL" } else { " AND wi.publishedfileid IS NULL" });
+        }
+        self.push_common_where(query, "addons", "addons.flags");
     }
 
-    pub fn get_sql(&self) -> String {
-        format!("{} {}", self.field, if self.descending { "DESC" } else { "ASC" })
+    /// Pushes " AND ..." conditions for a query on workshop_items, joined with workshop_files as wf
+    fn push_workshop_where(&self, query: &mut QueryBuilder<Sqlite>) {
+        if let Some(fts) = self.fts_query() {
+            query.push(" AND workshop_items.publishedfileid IN (SELECT rowid FROM workshop_search WHERE workshop_search MATCH ")
+                .push_bind(fts)
+                .push(")");
+        }
+        // Content flags are only known once the vpk has been scanned
+        self.push_common_where(query, "workshop_items", "(workshop_items.flags | coalesce(wf.flags, 0))");
+    }
+
+    fn push_common_where(&self, query: &mut QueryBuilder<Sqlite>, table: &str, flags_column: &str) {
+        if let Some(flags) = self.flags {
+            query.push(format!(" AND ({} & ", flags_column))
+                .push_bind(flags)
+                .push(") = ")
+                .push_bind(flags);
+        }
+        if let Some(min_size) = self.min_size {
+            query.push(format!(" AND {}.file_size >= ", table)).push_bind(min_size);
+        }
+        if let Some(max_size) = self.max_size {
+            query.push(format!(" AND {}.file_size <= ", table)).push_bind(max_size);
+        }
+    }
+
+    fn push_limit(&self, query: &mut QueryBuilder<Sqlite>) {
+        // sqlite needs a limit to use offset, -1 being no limit
+        query.push(" LIMIT ")
+            .push_bind(self.limit.map(|l| l as i64).unwrap_or(-1))
+            .push(" OFFSET ")
+            .push_bind(self.offset.unwrap_or(0));
+    }
+
+    /// Applies enabled filter & pagination on already fetched entries, as enabled state is not in db.
+    /// If no enabled filter is set, entries are expected to be paginated by query
+    fn apply_enabled(&self, entries: Vec<AddonEntry>) -> (Vec<AddonEntry>, Option<u32>) {
+        let Some(enabled) = self.enabled else {
+            return (entries, None);
+        };
+        let entries: Vec<AddonEntry> = entries
+            .into_iter()
+            .filter(|entry| entry.enabled == Some(enabled))
+            .collect();
+        let total = entries.len() as u32;
+        let entries = entries
+            .into_iter()
+            .skip(self.offset.unwrap_or(0) as usize)
+            .take(self.limit.map(|l| l as usize).unwrap_or(usize::MAX))
+            .collect();
+        (entries, Some(total))
     }
 }
 
+#[derive(Serialize)]
+/// A page of entries, from a filtered list
+pub struct AddonPage {
+    pub entries: Vec<AddonEntry>,
+    /// Number of entries matching the filter, across all pages
+    pub total: u32,
+}
+
 pub struct AddonStorage {
     pool: Pool<Sqlite>,
     db_path: PathBuf,
 }
 
-pub type AddonStorageContainer = Arc<Mutex<AddonStorage>>;
+/// Queries only need a read guard, the pool runs them concurrently. Write guard is for replacing or dropping the database
+pub type AddonStorageContainer = Arc<RwLock<AddonStorage>>;
+/// Number of past scans kept in scan history
+const MAX_SCAN_HISTORY: u32 = 50;
+/// How long a write waits for another connection's write to finish
+const DB_BUSY_TIMEOUT: Duration = Duration::from_secs(30);
 
 impl AddonStorage {
     pub async fn new(store_folder: PathBuf) -> Result<Self, String> {
         fs::create_dir_all(&store_folder).map_err(|e| e.to_string())?;
-        let db_path = store_folder.join("addon-manager.db");
+        Self::open(store_folder.join("addon-manager.db")).await
+    }
+
+    /// Opens database at the given file path, creating it if missing
+    pub async fn open(db_path: PathBuf) -> Result<Self, String> {
         let connection_options = sqlx::sqlite::SqliteConnectOptions::new()
             .filename(&db_path)
             .create_if_missing(true)
             .foreign_keys(true)
-            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+            // Writes from commands can wait on a scan's batched transaction
+            .busy_timeout(DB_BUSY_TIMEOUT);
         let pool = sqlx::sqlite::SqlitePool::connect_with(connection_options)
             .await
             .map_err(|e| e.to_string())?;
@@ -164,21 +344,25 @@ impl AddonStorage {
     pub async fn list(
         &self,
         addon_list: Option<AddonList>,
-        sort: Option<SelectedSort>
-    ) -> Result<Vec<AddonEntry>, sqlx::Error> {
-        // TODO: include workshop_items.*
-        let sort = sort.unwrap_or(SelectedSort { field: "title".to_string(), descending: false });
-        debug!("Sorting by {}", sort.get_sql());
-        Ok(sqlx::query_as::<_, StandardAddonWithTags>(
-            AssertSqlSafe(format!("
+        sort: Option<SelectedSort>,
+        filter: AddonFilter,
+    ) -> Result<AddonPage, ListError> {
+        let order_by = sort
+            .unwrap_or_else(|| SelectedSort::new("title", false))
+            .get_sql(SortTable::Addons)?;
+        debug!("Sorting by {} (filter={:?})", order_by, filter);
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new("
                 select addons.*, GROUP_CONCAT(tags.tag) tags
                 from addons
                 left join addon_tags tags on tags.hash = addons.file_hash
                 left join workshop_items wi on wi.publishedfileid = addons.workshop_id
-                group by addons.file_hash
-                order by {}
-            ", sort.get_sql())),
-        )
+                where 1=1");
+        filter.push_addons_where(&mut query);
+        query.push(" group by addons.file_hash order by ").push(order_by);
+        if filter.enabled.is_none() {
+            filter.push_limit(&mut query);
+        }
+        let entries = query.build_query_as::<StandardAddonWithTags>()
         .fetch_all(&self.pool)
         .await?
         .into_iter()
@@ -193,59 +377,159 @@ impl AddonStorage {
                 id: entry.file_hash.to_string(),
                 enabled: addon_list
                     .as_ref()
-                    .map(|list| list.is_enabled(&entry.data.filename)),
+                    .zip(entry.data.filename.as_ref())
+                    .map(|(list, filename)| list.is_enabled(filename)),
                 info: entry.data,
                 workshop: None,
                 tags,
             }
         })
-        .collect::<Vec<AddonEntry>>())
+        .collect::<Vec<AddonEntry>>();
+
+        // Attach linked workshop items
+        let workshop_ids: Vec<i64> = entries.iter().filter_map(|entry| entry.info.workshop_id).collect();
+        let workshop_items = self.get_workshop_entries(&workshop_ids).await?;
+        let entries = entries
+            .into_iter()
+            .map(|mut entry| {
+                entry.workshop = entry.info.workshop_id.and_then(|id| workshop_items.get(&id).cloned());
+                entry
+            })
+            .collect::<Vec<AddonEntry>>();
+
+        let (entries, total) = filter.apply_enabled(entries);
+        let total = match total {
+            Some(total) => total,
+            None => {
+                let mut count_query: QueryBuilder<Sqlite> = QueryBuilder::new("
+                    select count(*)
+                    from addons
+                    left join workshop_items wi on wi.publishedfileid = addons.workshop_id
+                    where 1=1");
+                filter.push_addons_where(&mut count_query);
+                count_query.build_query_scalar::<u32>().fetch_one(&self.pool).await?
+            }
+        };
+        Ok(AddonPage { entries, total })
     }
 
     pub async fn list_workshop(
         &self,
         addon_list: Option<AddonList>,
-        sort: Option<SelectedSort>
-    ) -> Result<Vec<AddonEntry>, sqlx::Error> {
+        sort: Option<SelectedSort>,
+        filter: AddonFilter,
+    ) -> Result<AddonPage, ListError> {
         // flags & 1 marks AddonFlags::WORKSHOP
-        let sort = sort.unwrap_or(SelectedSort { field: "time_updated".to_string(), descending: true });
-        debug!("Sorting by {}", sort.get_sql());
-        Ok(sqlx::query_as::<_, WorkshopEntry>(
-            AssertSqlSafe(format!(r#"
-                select *
+        let order_by = sort
+            .unwrap_or_else(|| SelectedSort::new("time_updated", true))
+            .get_sql(SortTable::Workshop)?;
+        debug!("Sorting by {} (filter={:?})", order_by, filter);
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new(r#"
+                select workshop_items.*, wf.flags as file_flags, wf.author as file_author,
+                    wf.version as file_version, wf.tagline as file_tagline, wf.chapter_ids as file_chapter_ids
                 from workshop_items
-                where flags & 1
-                order by {}
-            "#, sort.get_sql()))
-        )
+                left join workshop_files wf on wf.publishedfileid = workshop_items.publishedfileid
+                where workshop_items.flags & 1"#);
+        filter.push_workshop_where(&mut query);
+        query.push(" order by ").push(order_by);
+        if filter.enabled.is_none() {
+            filter.push_limit(&mut query);
+        }
+        let entries = query.build_query_as::<WorkshopEntryWithFile>()
         .fetch_all(&self.pool)
         .await?
         .into_iter()
-        .map(|entry| AddonEntry {
-            id: entry.publishedfileid.to_string(),
-            enabled: addon_list
-                .as_ref()
-                .map(|list| list.is_enabled(&format!("workshop\\{}.vpk", entry.publishedfileid))),
-            info: AddonData {
-                filename: format!("{}.vpk", entry.publishedfileid),
-                created_at: chrono::DateTime::from_timestamp_secs(entry.time_created).unwrap(),
-                updated_at: chrono::DateTime::from_timestamp_secs(
-                    *entry.time_updated.as_ref().unwrap(),
-                )
-                .unwrap(),
-                file_size: entry.file_size as i64,
-                flags: AddonFlags(0),
-                title: entry.title.clone(),
-                author: Some(entry.creator_id.to_string()),
-                version: Some("workshop".to_string()),
-                tagline: None,
-                chapter_ids: None,
-                workshop_id: Some(entry.publishedfileid as i64),
-            },
-            tags: vec![], // not supported yet (needs own table?)
-            workshop: Some(entry),
+        .map(|row| {
+            let entry = row.entry;
+            AddonEntry {
+                id: entry.publishedfileid.to_string(),
+                enabled: addon_list
+                    .as_ref()
+                    .map(|list| list.is_enabled(&format!("workshop\\{}.vpk", entry.publishedfileid))),
+                info: AddonData {
+                    filename: Some(format!("{}.vpk", entry.publishedfileid)),
+                    created_at: chrono::DateTime::from_timestamp_secs(entry.time_created).unwrap(),
+                    updated_at: chrono::DateTime::from_timestamp_secs(
+                        *entry.time_updated.as_ref().unwrap(),
+                    )
+                    .unwrap(),
+                    file_size: entry.file_size as i64,
+                    // Info from vpk if it has been scanned, otherwise what workshop has
+                    flags: AddonFlags::WORKSHOP | AddonFlags(row.file_flags.unwrap_or(0)),
+                    title: entry.title.clone(),
+                    author: row.file_author.or_else(|| Some(entry.creator_id.to_string())),
+                    version: row.file_version.or_else(|| Some("workshop".to_string())),
+                    tagline: row.file_tagline,
+                    chapter_ids: row.file_chapter_ids,
+                    workshop_id: Some(entry.publishedfileid as i64),
+                },
+                tags: vec![], // not supported yet (needs own table?)
+                workshop: Some(entry),
+            }
         })
-        .collect::<Vec<AddonEntry>>())
+        .collect::<Vec<AddonEntry>>();
+
+        let (entries, total) = filter.apply_enabled(entries);
+        let total = match total {
+            Some(total) => total,
+            None => {
+                let mut count_query: QueryBuilder<Sqlite> = QueryBuilder::new("
+                    select count(*)
+                    from workshop_items
+                    left join workshop_files wf on wf.publishedfileid = workshop_items.publishedfileid
+                    where workshop_items.flags & 1");
+                filter.push_workshop_where(&mut count_query);
+                count_query.build_query_scalar::<u32>().fetch_one(&self.pool).await?
+            }
+        };
+        Ok(AddonPage { entries, total })
+    }
+
+    /// Returns the workshop items of the given ids that exist, keyed by id
+    async fn get_workshop_entries(&self, ids: &[i64]) -> Result<HashMap<i64, WorkshopEntry>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new("select * from workshop_items where publishedfileid in (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+        query.push(")");
+        Ok(query.build_query_as::<WorkshopEntry>()
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|entry| (entry.publishedfileid as i64, entry))
+            .collect())
+    }
+
+    /// Returns the distinct workshop ids managed addons are linked to
+    pub async fn list_managed_workshop_ids(&self) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>("select distinct workshop_id from addons where workshop_id is not null")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Returns (hash, filename, title, workshop id) of every managed addon with a file that is linked to a workshop item
+    pub async fn list_workshop_linked(&self) -> Result<Vec<(FileHash, String, String, i64)>, sqlx::Error> {
+        sqlx::query_as::<_, (FileHash, String, String, i64)>(
+            "select file_hash, filename, title, workshop_id from addons where filename is not null and workshop_id is not null"
+        )
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Returns filename of addon and download url of the workshop item it is linked to
+    pub async fn get_workshop_file_url(&self, hash: &FileHash) -> Result<Option<(String, String)>, sqlx::Error> {
+        sqlx::query_as::<_, (String, String)>(
+            "select addons.filename, workshop_items.file_url from addons \
+             join workshop_items on workshop_items.publishedfileid = addons.workshop_id \
+             where addons.file_hash = ? and addons.filename is not null"
+        )
+            .bind(hash.clone())
+            .fetch_optional(&self.pool)
+            .await
     }
 
     pub async fn list_workshop_ids(&self) -> Result<Vec<i64>, sqlx::Error> {
@@ -260,48 +544,95 @@ impl AddonStorage {
         .await
     }
 
+    /// Returns workshop ids of addons that have no workshop item yet, such as ones whose fetch failed
+    pub async fn list_unfetched_workshop_ids(&self) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(
+            "select distinct workshop_id from addons where workshop_id is not null \
+             and workshop_id not in (select publishedfileid from workshop_items)"
+        )
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Returns the stored file stamp of every addon that has a file, keyed by filename
+    pub async fn list_file_stamps(&self) -> Result<HashMap<String, FileStamp>, sqlx::Error> {
+        Ok(sqlx::query_as::<_, (String, i64, Option<i64>)>(
+            // Addons not indexed yet are left out, so they are rescanned and indexed
+            "select filename, file_size, file_mtime from addons where filename is not null and files_indexed = 1"
+        )
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|(filename, file_size, file_mtime)| (filename, FileStamp { file_size, file_mtime }))
+            .collect())
+    }
+
+    /// Marks the entries as seen by scan, without touching any of their info.
+    /// Used for files that were skipped as they have not changed since last scan
+    pub async fn touch_entries(&self, filenames: &[String], scan_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin().await?;
+        for filename in filenames {
+            sqlx::query!("UPDATE addons SET scan_id = ? WHERE filename = ?", scan_id, filename)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    /// Starts a write transaction, for storing many changes in one commit. The write lock is taken immediately,
+    /// as a deferred transaction that later writes fails with SQLITE_BUSY instead of waiting if another write started first
+    pub async fn begin(&self) -> Result<Transaction<'static, Sqlite>, sqlx::Error> {
+        self.pool.begin_with("BEGIN IMMEDIATE").await
+    }
+
     /// Update the entry by its hash. Returns boolean if an entry existed and had its filename & content changed, false if not
     pub async fn update_entry_by_hash(
-        &mut self,
+        conn: &mut SqliteConnection,
         hash: &FileHash,
         new_filename: &str,
         info: &AddonInfo,
+        stamp: &FileStamp,
         scan_id: Option<u32>,
     ) -> Result<bool, sqlx::Error> {
         let flags: AddonFlags = (&info.content).into();
         let affected = sqlx::query!(
-            "UPDATE addons SET filename = ?, title = ?, version = ?, flags = ?, scan_id = ? WHERE file_hash = ?",
+            "UPDATE addons SET filename = ?, title = ?, version = ?, flags = ?, file_size = ?, file_mtime = ?, scan_id = ? WHERE file_hash = ?",
             new_filename,
             info.title,
             info.version,
             flags.0,
+            stamp.file_size,
+            stamp.file_mtime,
             scan_id,
             hash,
         )
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await?
             .rows_affected();
         Ok(affected > 0)
     }
 
     pub async fn update_entry_by_filename (
-        &mut self,
+        conn: &mut SqliteConnection,
         new_hash: &FileHash,
         filename: &str,
         info: &AddonInfo,
+        stamp: &FileStamp,
         scan_id: Option<u32>,
     ) -> Result<bool, sqlx::Error> {
         let flags: AddonFlags = (&info.content).into();
         let affected = sqlx::query!(
-            "UPDATE addons SET file_hash = ?, title = ?, version = ?, flags = ?, scan_id = ? WHERE filename = ?",
+            "UPDATE addons SET file_hash = ?, title = ?, version = ?, flags = ?, file_size = ?, file_mtime = ?, scan_id = ? WHERE filename = ?",
             new_hash,
             info.title,
             info.version,
             flags.0,
+            stamp.file_size,
+            stamp.file_mtime,
             scan_id,
             filename,
         )
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await?
             .rows_affected();
         Ok(affected > 0)
@@ -309,20 +640,22 @@ impl AddonStorage {
 
     /// Adds a new entry to database
     pub async fn add_entry(
-        &self,
+        conn: &mut SqliteConnection,
         addon: &AddonData,
+        file_mtime: Option<i64>,
         scan_id: Option<u32>,
         hash: FileHash,
     ) -> Result<(), sqlx::Error> {
         sqlx::query!(
             r#"INSERT INTO addons
-                (filename, updated_at, created_at, file_size, title, author, version, tagline, flags, workshop_id, scan_id, file_hash)
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                (filename, updated_at, created_at, file_size, file_mtime, title, author, version, tagline, flags, workshop_id, scan_id, file_hash)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             addon.filename,
             addon.updated_at,
             addon.created_at,
             addon.file_size,
+            file_mtime,
             addon.title,
             addon.author,
             addon.version,
@@ -331,9 +664,9 @@ impl AddonStorage {
             addon.workshop_id,
             scan_id,
             hash
-        ).execute(&self.pool).await?;
+        ).execute(&mut *conn).await?;
         info!(
-            "Added entry {} (flags={}) (ws_id={:?}) (title={})",
+            "Added entry {:?} (flags={}) (ws_id={:?}) (title={})",
             addon.filename, addon.flags.0, addon.workshop_id, addon.title
         );
         Ok(())
@@ -345,7 +678,7 @@ impl AddonStorage {
             return Ok(());
         }
         let mut query_builder: QueryBuilder<Sqlite> = QueryBuilder::new(
-            "INSERT OR REPLACE INTO workshop_items (publishedfileid, title, time_created, time_updated, file_size, description, file_url, creator_id, tags) ",
+            "INSERT INTO workshop_items (publishedfileid, title, time_created, time_updated, file_size, description, file_url, creator_id, tags, preview_url) ",
         );
         let num_items = items.len();
         query_builder.push_values(items, |mut b, item| {
@@ -363,15 +696,124 @@ impl AddonStorage {
                         .map(|tag| tag.tag.clone())
                         .collect::<Vec<String>>()
                         .join(","),
-                );
+                )
+                .push_bind(Some(item.preview_url).filter(|url| !url.is_empty()));
         });
 
+        // Keep flags of existing items
+        query_builder.push(
+            " ON CONFLICT (publishedfileid) DO UPDATE SET title = excluded.title, time_created = excluded.time_created, \
+              time_updated = excluded.time_updated, file_size = excluded.file_size, description = excluded.description, \
+              file_url = excluded.file_url, creator_id = excluded.creator_id, tags = excluded.tags, \
+              preview_url = excluded.preview_url"
+        );
         let query = query_builder.build();
         query.execute(&self.pool).await?;
         info!("Added {} workshop items to database", num_items);
         Ok(())
     }
 
+    /// Returns the stored file stamp of every scanned workshop folder vpk, keyed by workshop id
+    pub async fn list_workshop_file_stamps(&self) -> Result<HashMap<i64, FileStamp>, sqlx::Error> {
+        Ok(sqlx::query_as::<_, (i64, i64, Option<i64>)>(
+            "select publishedfileid, file_size, file_mtime from workshop_files"
+        )
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|(id, file_size, file_mtime)| (id, FileStamp { file_size, file_mtime }))
+            .collect())
+    }
+
+    /// Adds or updates the parsed info of a workshop folder vpk. Returns true if it was already stored
+    pub async fn set_workshop_file(
+        conn: &mut SqliteConnection,
+        workshop_id: i64,
+        hash: &FileHash,
+        info: &AddonInfo,
+        chapter_ids: Option<String>,
+        stamp: &FileStamp,
+    ) -> Result<bool, sqlx::Error> {
+        let flags: AddonFlags = (&info.content).into();
+        let existed = sqlx::query_scalar::<_, i64>("select count(*) from workshop_files where publishedfileid = ?")
+            .bind(workshop_id)
+            .fetch_one(&mut *conn)
+            .await? > 0;
+        sqlx::query!(
+            r#"INSERT INTO workshop_files
+                (publishedfileid, file_hash, file_size, file_mtime, title, author, version, tagline, chapter_ids, flags)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (publishedfileid) DO UPDATE SET
+                    file_hash = excluded.file_hash, file_size = excluded.file_size, file_mtime = excluded.file_mtime,
+                    title = excluded.title, author = excluded.author, version = excluded.version,
+                    tagline = excluded.tagline, chapter_ids = excluded.chapter_ids, flags = excluded.flags
+            "#,
+            workshop_id,
+            hash,
+            stamp.file_size,
+            stamp.file_mtime,
+            info.title,
+            info.author,
+            info.version,
+            info.tagline,
+            chapter_ids,
+            flags.0,
+        )
+            .execute(&mut *conn)
+            .await?;
+        Ok(existed)
+    }
+
+    /// Removes the parsed info of workshop folder vpks that are no longer in the folder
+    pub async fn delete_workshop_files_except(&self, ids: &[i64]) -> Result<(), sqlx::Error> {
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new("DELETE FROM workshop_files WHERE publishedfileid NOT IN (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+        query.push(")");
+        query.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Returns hash of a scanned workshop folder vpk
+    pub async fn get_workshop_file_hash(&self, workshop_id: i64) -> Result<Option<FileHash>, sqlx::Error> {
+        sqlx::query_scalar::<_, FileHash>("select file_hash from workshop_files where publishedfileid = ?")
+            .bind(workshop_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Returns the workshop preview image of a managed addon or workshop folder vpk, if it has a workshop item
+    pub async fn get_preview_url(&self, hash: &FileHash) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, String>(
+            "select preview_url from workshop_items where preview_url is not null and publishedfileid in \
+             (select workshop_id from addons where file_hash = ? union select publishedfileid from workshop_files where file_hash = ?)"
+        )
+            .bind(hash.clone())
+            .bind(hash.clone())
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Returns hash of every managed addon and scanned workshop folder vpk
+    pub async fn list_known_hashes(&self) -> Result<Vec<FileHash>, sqlx::Error> {
+        sqlx::query_scalar::<_, FileHash>("select file_hash from addons union select file_hash from workshop_files")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Scan id to store entries with outside of a scan, such as by the watcher. A scan that is running, paused
+    /// or waiting to be resumed marks every entry without its id as missing when it ends, so its id is used if there is one
+    pub async fn current_scan_id(&self) -> Result<u32, sqlx::Error> {
+        let running = sqlx::query_scalar::<_, i64>(
+            "select id from scans where status = 'running' order by started_at desc limit 1"
+        )
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(running.map(|id| id as u32).unwrap_or_else(random))
+    }
+
     /// Sets filenames to null for any entry that does not match scan_id
     /// To be called at end of scan
     pub async fn scan_mark_missing(&self, id: u32) -> Result<(), sqlx::Error> {
@@ -381,15 +823,239 @@ impl AddonStorage {
         Ok(())
     }
 
-    pub async fn mark_workshop_ids(&self, ids: Vec<i64>) -> Result<(), sqlx::Error> {
-        if ids.is_empty() {
-            return Ok(());
+    /// Records a scan as running. Scans still marked running were interrupted, such as by the app closing.
+    /// Only the latest MAX_SCAN_HISTORY scans are kept
+    pub async fn start_scan_record(&self, id: u32, speed: &str) -> Result<(), sqlx::Error> {
+        let now = Utc::now().timestamp();
+        let mut tx = self.begin().await?;
+        sqlx::query!("DELETE FROM scan_queue WHERE scan_id IN (SELECT id FROM scans WHERE status = 'running')")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "UPDATE scans SET status = 'aborted', aborted_reason = coalesce(aborted_reason, 'interrupted') WHERE status = 'running'"
+        )
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "INSERT OR REPLACE INTO scans (id, started_at, speed, status) VALUES (?, ?, ?, 'running')",
+            id, now, speed
+        )
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "DELETE FROM scans WHERE source = 'scan' \
+            AND id NOT IN (SELECT id FROM scans WHERE source = 'scan' ORDER BY started_at DESC LIMIT ?)"
+        )
+            .bind(MAX_SCAN_HISTORY)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Records end of a scan, adding its counts and the files that failed. Its queue is cleared
+    pub async fn finish_scan_record(
+        &self,
+        id: u32,
+        status: ScanStatus,
+        counter: &ScanCounter,
+        errors: &[ScanError],
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now().timestamp();
+        let mut tx = self.begin().await?;
+        sqlx::query!(
+            "UPDATE scans SET status = ?, ended_at = ?, total = total + ?, added = added + ?, updated = updated + ?, \
+            skipped = skipped + ?, failed = failed + ? WHERE id = ?",
+            status, now, counter.total, counter.added, counter.updated, counter.skipped, counter.errors, id
+        )
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM scan_queue WHERE scan_id = ?", id)
+            .execute(&mut *tx)
+            .await?;
+        for error in errors {
+            sqlx::query!(
+                "INSERT INTO scan_errors (scan_id, filename, workshop_id, error) VALUES (?, ?, ?, ?)",
+                id, error.filename, error.workshop_id, error.error
+            )
+                .execute(&mut *tx)
+                .await?;
+        }
+        // Files that failed outside of the scan were scanned again by it
+        if status == ScanStatus::Complete {
+            sqlx::query(
+                "DELETE FROM scans WHERE source != 'scan' AND started_at <= (SELECT started_at FROM scans WHERE id = ?)"
+            )
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    /// Records files that were stored or failed to be stored outside of a scan, by source ('watcher', 'copy' or
+    /// 'update'), so list_broken_files includes the failures until the next complete scan. Errors are stored under
+    /// a new record of the given id, and earlier errors of the stored and failed files are removed
+    pub async fn record_outside_scan(
+        &self,
+        id: u32,
+        source: &str,
+        stored: &[String],
+        errors: &[ScanError],
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now().timestamp();
+        let mut tx = self.begin().await?;
+        for filename in stored.iter().chain(errors.iter().map(|error| &error.filename)) {
+            sqlx::query(
+                "DELETE FROM scan_errors WHERE filename = ? AND scan_id IN (SELECT id FROM scans WHERE source != 'scan')"
+            )
+                .bind(filename)
+                .execute(&mut *tx)
+                .await?;
+        }
+        if !errors.is_empty() {
+            sqlx::query(
+                "INSERT OR REPLACE INTO scans (id, started_at, ended_at, speed, status, total, failed, source) \
+                VALUES (?, ?, ?, ?, 'complete', ?, ?, ?)"
+            )
+                .bind(id)
+                .bind(now)
+                .bind(now)
+                .bind(source)
+                .bind((stored.len() + errors.len()) as i64)
+                .bind(errors.len() as i64)
+                .bind(source)
+                .execute(&mut *tx)
+                .await?;
+            for error in errors {
+                sqlx::query("INSERT INTO scan_errors (scan_id, filename, workshop_id, error) VALUES (?, ?, ?, ?)")
+                    .bind(id)
+                    .bind(&error.filename)
+                    .bind(error.workshop_id)
+                    .bind(&error.error)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        // Records whose errors were all fixed
+        sqlx::query(
+            "DELETE FROM scans WHERE source != 'scan' AND NOT EXISTS (SELECT 1 FROM scan_errors WHERE scan_id = scans.id)"
+        )
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Stores files scan has to process, as (path, workshop id)
+    pub async fn set_scan_queue(&self, id: u32, files: &[(PathBuf, Option<i64>)]) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin().await?;
+        for (path, workshop_id) in files {
+            let path = path.to_string_lossy();
+            sqlx::query!(
+                "INSERT OR IGNORE INTO scan_queue (scan_id, path, workshop_id) VALUES (?, ?, ?)",
+                id, path, workshop_id
+            )
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    /// Removes processed files from scan's queue
+    pub async fn remove_scan_queue(conn: &mut SqliteConnection, id: u32, paths: &[PathBuf]) -> Result<(), sqlx::Error> {
+        for path in paths {
+            let path = path.to_string_lossy();
+            sqlx::query!("DELETE FROM scan_queue WHERE scan_id = ? AND path = ?", id, path)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Returns files scan has yet to process, as (path, workshop id)
+    pub async fn list_scan_queue(&self, id: u32) -> Result<Vec<(PathBuf, Option<i64>)>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (String, Option<i64>)>("select path, workshop_id from scan_queue where scan_id = ?")
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(|(path, workshop_id)| (PathBuf::from(path), workshop_id)).collect())
+    }
+
+    /// Returns latest scan that never ended and has files left, such as one interrupted by the app closing
+    pub async fn get_unfinished_scan(&self) -> Result<Option<ScanRecord>, sqlx::Error> {
+        sqlx::query_as::<_, ScanRecord>(
+            "select * from scans where status = 'running' \
+            and exists (select 1 from scan_queue where scan_queue.scan_id = scans.id) \
+            order by started_at desc limit 1"
+        )
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn set_scan_aborted_reason(&self, id: u32, reason: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!("UPDATE scans SET aborted_reason = ? WHERE id = ?", reason, id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_scan(&self, id: u32) -> Result<Option<ScanRecord>, sqlx::Error> {
+        sqlx::query_as::<_, ScanRecord>("select * from scans where id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Returns latest scans, newest first
+    pub async fn list_scans(&self, limit: u32) -> Result<Vec<ScanRecord>, sqlx::Error> {
+        sqlx::query_as::<_, ScanRecord>("select * from scans where source = 'scan' order by started_at desc limit ?")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn list_scan_errors(&self, id: i64) -> Result<Vec<ScanError>, sqlx::Error> {
+        sqlx::query_as::<_, ScanError>(
+            "select scan_id, filename, workshop_id, error from scan_errors where scan_id = ? order by filename"
+        )
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Returns files that failed in the latest complete scan. Broken files are never stored,
+    /// so they are rescanned and reported again by every scan until fixed or removed
+    pub async fn list_broken_files(&self) -> Result<Vec<ScanError>, sqlx::Error> {
+        sqlx::query_as::<_, ScanError>(
+            "select scan_id, filename, workshop_id, error from scan_errors \
+            where scan_id = (select id from scans where status = 'complete' and source = 'scan' order by started_at desc limit 1) \
+            or scan_id in (select id from scans where source != 'scan') \
+            order by filename"
+        )
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Sets filenames to null for the given filenames, for files that have been removed
+    pub async fn mark_missing_filenames(&self, filenames: &[String]) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin().await?;
+        for filename in filenames {
+            sqlx::query!("UPDATE addons SET filename = NULL WHERE filename = ?", filename)
+                .execute(&mut *tx)
+                .await?;
         }
-        let mut tx = self.pool.begin().await?;
+        tx.commit().await
+    }
+
+    pub async fn mark_workshop_ids(&self, ids: Vec<i64>) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin().await?;
         // Drop AddonFlags::WORKSHOP for all items
         sqlx::query!("UPDATE workshop_items SET flags=flags&~1 WHERE flags & 1")
             .execute(&mut *tx)
             .await?;
+        // Workshop folder is empty, nothing to set
+        if ids.is_empty() {
+            return tx.commit().await;
+        }
         // Set all given ids to include AddonFlags::WORKSHOP
         let params = format!("?{}", ", ?".repeat(ids.len() - 1));
         let mut query = sqlx::query(AssertSqlSafe(format!(
@@ -436,9 +1102,237 @@ impl AddonStorage {
             .map(|_| ())
     }
 
+    /// Returns (hash, filename) of every managed addon, filename is None if file is missing
+    pub async fn list_hash_filenames(&self) -> Result<Vec<(FileHash, Option<String>)>, sqlx::Error> {
+        sqlx::query_as::<_, (FileHash, Option<String>)>("select file_hash, filename from addons")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Returns (filename, workshop id) of every managed addon that has a file
+    pub async fn list_filenames_workshop_ids(&self) -> Result<Vec<(String, Option<i64>)>, sqlx::Error> {
+        sqlx::query_as::<_, (String, Option<i64>)>(
+            "select filename, workshop_id from addons where filename is not null"
+        )
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Replaces the stored internal file list of addon, and marks it as indexed. Empty if its vpk tree could not be read
+    pub async fn set_addon_files(conn: &mut SqliteConnection, hash: &FileHash, files: &[VpkFile]) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM addon_files WHERE hash = ?", hash)
+            .execute(&mut *conn)
+            .await?;
+        for file in files {
+            let file_size = file.size as i64;
+            sqlx::query!(
+                "INSERT OR IGNORE INTO addon_files (hash, path, file_size, crc) VALUES (?, ?, ?, ?)",
+                hash, file.path, file_size, file.crc
+            )
+                .execute(&mut *conn)
+                .await?;
+        }
+        sqlx::query("UPDATE addons SET files_indexed = 1 WHERE file_hash = ?")
+            .bind(hash.clone())
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns (hash, hash, path) of every file path that exists in two of the given addons.
+    /// Files in the root of vpk (addoninfo.txt, addonimage.jpg) are not mounted, and are ignored
+    pub async fn list_file_overlaps(&self, hashes: &[FileHash]) -> Result<Vec<(FileHash, FileHash, String)>, sqlx::Error> {
+        if hashes.len() < 2 {
+            return Ok(Vec::new());
+        }
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "with enabled as (select hash, path from addon_files where path like '%/%' and hash in ("
+        );
+        let mut separated = query.separated(", ");
+        for hash in hashes {
+            separated.push_bind(hash.clone());
+        }
+        query.push(
+            ")) select a.hash, b.hash, a.path from enabled a join enabled b on a.path = b.path and a.hash < b.hash order by a.path"
+        );
+        query.build_query_as::<(FileHash, FileHash, String)>()
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Returns filename of addon, None if addon does not exist or its file is missing
+    pub async fn get_filename(&self, hash: &FileHash) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, Option<String>>("select filename from addons where file_hash = ?")
+            .bind(hash.clone())
+            .fetch_optional(&self.pool)
+            .await
+            .map(Option::flatten)
+    }
+
+    /// Returns (hash, filename, title) of every managed addon that has a file
+    pub async fn list_hash_filename_titles(&self) -> Result<Vec<(FileHash, String, String)>, sqlx::Error> {
+        sqlx::query_as::<_, (FileHash, String, String)>(
+            "select file_hash, filename, title from addons where filename is not null"
+        )
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn list_profiles(&self) -> Result<Vec<Profile>, sqlx::Error> {
+        sqlx::query_as::<_, Profile>("select * from profiles order by name")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn get_profile(&self, id: i64) -> Result<Option<Profile>, sqlx::Error> {
+        sqlx::query_as::<_, Profile>("select * from profiles where id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn get_profile_items(&self, id: i64) -> Result<ProfileItems, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (Option<FileHash>, Option<i64>)>(
+            "select file_hash, workshop_id from profile_items where profile_id = ?"
+        )
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await?;
+        let mut items = ProfileItems::default();
+        for (hash, workshop_id) in rows {
+            if let Some(hash) = hash {
+                items.ids.push(hash.to_string());
+            }
+            if let Some(workshop_id) = workshop_id {
+                items.workshop_ids.push(workshop_id);
+            }
+        }
+        Ok(items)
+    }
+
+    /// Creates a new profile, returning its id
+    pub async fn create_profile(
+        &self,
+        name: &str,
+        hashes: &[FileHash],
+        workshop_ids: &[i64],
+    ) -> Result<i64, sqlx::Error> {
+        let now = Utc::now().timestamp();
+        let mut tx = self.begin().await?;
+        let id = sqlx::query!(
+            "INSERT INTO profiles (name, created_at, updated_at) VALUES (?, ?, ?)",
+            name, now, now
+        )
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+        Self::insert_profile_items(&mut *tx, id, hashes, workshop_ids).await?;
+        tx.commit().await?;
+        info!("Created profile {} \"{}\" ({} addons, {} workshop)", id, name, hashes.len(), workshop_ids.len());
+        Ok(id)
+    }
+
+    /// Updates name and/or replaces items of profile. Returns false if profile does not exist
+    pub async fn update_profile(
+        &self,
+        id: i64,
+        name: Option<&str>,
+        items: Option<(&[FileHash], &[i64])>,
+    ) -> Result<bool, sqlx::Error> {
+        let now = Utc::now().timestamp();
+        let mut tx = self.begin().await?;
+        let affected = sqlx::query!(
+            "UPDATE profiles SET name = coalesce(?, name), updated_at = ? WHERE id = ?",
+            name, now, id
+        )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if affected == 0 {
+            return Ok(false);
+        }
+        if let Some((hashes, workshop_ids)) = items {
+            sqlx::query!("DELETE FROM profile_items WHERE profile_id = ?", id)
+                .execute(&mut *tx)
+                .await?;
+            Self::insert_profile_items(&mut *tx, id, hashes, workshop_ids).await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Copies profile and its items under a new name, returning the new profile's id
+    pub async fn duplicate_profile(&self, id: i64, name: &str) -> Result<i64, sqlx::Error> {
+        let now = Utc::now().timestamp();
+        let mut tx = self.begin().await?;
+        let new_id = sqlx::query!(
+            "INSERT INTO profiles (name, created_at, updated_at) VALUES (?, ?, ?)",
+            name, now, now
+        )
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+        sqlx::query!(
+            "INSERT INTO profile_items (profile_id, file_hash, workshop_id) SELECT ?, file_hash, workshop_id FROM profile_items WHERE profile_id = ?",
+            new_id, id
+        )
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(new_id)
+    }
+
+    pub async fn delete_profile(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let affected = sqlx::query!("DELETE FROM profiles WHERE id = ?", id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(affected > 0)
+    }
+
+    async fn insert_profile_items(
+        conn: &mut SqliteConnection,
+        id: i64,
+        hashes: &[FileHash],
+        workshop_ids: &[i64],
+    ) -> Result<(), sqlx::Error> {
+        for hash in hashes {
+            sqlx::query!(
+                "INSERT OR IGNORE INTO profile_items (profile_id, file_hash) VALUES (?, ?)",
+                id, hash
+            )
+                .execute(&mut *conn)
+                .await?;
+        }
+        for workshop_id in workshop_ids {
+            sqlx::query!(
+                "INSERT OR IGNORE INTO profile_items (profile_id, workshop_id) VALUES (?, ?)",
+                id, workshop_id
+            )
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
     /// Wipes all data from database
-    pub async fn danger_drop_database(&self) -> Result<(), std::io::Error> {
+    pub async fn danger_drop_database(&mut self) -> Result<(), std::io::Error> {
         self.pool.close().await;
         fs::remove_file(&self.db_path)
     }
+
+    /// Closes database and replaces its file with the given database file.
+    /// The new database should be closed, any WAL changes of the current database are discarded
+    pub async fn danger_replace_database(&mut self, new_db_path: &PathBuf) -> Result<(), std::io::Error> {
+        self.pool.close().await;
+        for suffix in ["-wal", "-shm"] {
+            let mut path = self.db_path.clone().into_os_string();
+            path.push(suffix);
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        fs::rename(new_db_path, &self.db_path)
+    }
 }
@@ -0,0 +1,142 @@
+use crate::modules::store::FileHash;
+use crate::modules::vpk::VpkDirectory;
+use crate::modules::vtf;
+use image::codecs::jpeg::JpegEncoder;
+use log::{debug, warn};
+use sqlx::__rt::spawn_blocking;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+/// Max width and height of a thumbnail, aspect ratio is kept
+const THUMBNAIL_SIZE: u32 = 256;
+const THUMBNAIL_QUALITY: u8 = 85;
+/// Images larger than this are ignored, addon images are small
+const MAX_IMAGE_SIZE: u64 = 10 * 1024 * 1024;
+/// Image files checked inside of a vpk, in order
+const VPK_IMAGE_NAMES: [&str; 3] = ["addonimage.jpg", "addonimage.png", "addonimage.vtf"];
+/// Extensions checked for an image next to the vpk, in order
+const SIDE_IMAGE_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+#[derive(Clone)]
+/// Downscaled addon images, stored as {hash}.jpg in the installation's store folder
+pub struct ThumbnailCache {
+    folder: Arc<RwLock<PathBuf>>,
+}
+
+impl ThumbnailCache {
+    pub fn new(store_folder: &PathBuf) -> Self {
+        ThumbnailCache {
+            folder: Arc::new(RwLock::new(store_folder.join("thumbnails"))),
+        }
+    }
+
+    /// Switches to another installation's thumbnails, for every clone of the cache
+    pub fn set_store_folder(&self, store_folder: &PathBuf) {
+        *self.folder.write().unwrap() = store_folder.join("thumbnails");
+    }
+
+    fn folder(&self) -> PathBuf {
+        self.folder.read().unwrap().clone()
+    }
+
+    pub fn path(&self, hash: &FileHash) -> PathBuf {
+        self.folder().join(format!("{}.jpg", hash))
+    }
+
+    pub fn has(&self, hash: &FileHash) -> bool {
+        self.path(hash).exists()
+    }
+
+    /// Returns the thumbnail of addon, None if it has none
+    pub fn get(&self, hash: &FileHash) -> Option<Vec<u8>> {
+        std::fs::read(self.path(hash)).ok()
+    }
+
+    /// Downscales image (jpg, png, or vtf) and stores it as addon's thumbnail
+    pub fn save(&self, hash: &FileHash, image: &[u8]) -> Result<(), String> {
+        let image = match vtf::is_vtf(image) {
+            true => vtf::decode(image).map_err(|e| format!("decode vtf: {}", e))?,
+            false => image::load_from_memory(image).map_err(|e| format!("decode image: {}", e))?,
+        };
+        let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8();
+        std::fs::create_dir_all(self.folder()).map_err(|e| format!("create cache folder: {}", e))?;
+        // Write to temp file first, so a partial thumbnail is never served
+        let dest = self.path(hash);
+        let tmp_dest = dest.with_extension("jpg.tmp");
+        let result = File::create(&tmp_dest)
+            .map_err(|e| e.to_string())
+            .and_then(|file| {
+                JpegEncoder::new_with_quality(BufWriter::new(file), THUMBNAIL_QUALITY)
+                    .encode_image(&thumbnail)
+                    .map_err(|e| e.to_string())
+            })
+            .and_then(|_| std::fs::rename(&tmp_dest, &dest).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            std::fs::remove_file(&tmp_dest).ok();
+            return Err(format!("save thumbnail: {}", e));
+        }
+        Ok(())
+    }
+
+    /// Creates addon's thumbnail from the image found by find_addon_image, logging any error
+    pub fn create(&self, hash: &FileHash, image: &[u8]) {
+        match self.save(hash, image) {
+            Ok(()) => debug!("created thumbnail for {}", hash),
+            Err(e) => warn!("thumbnail for {}: {}", hash, e),
+        }
+    }
+
+    /// Downloads the workshop preview image of an addon without an image of its own, and stores it as its thumbnail.
+    /// Returns the thumbnail
+    pub async fn fetch_preview(&self, hash: &FileHash, url: &str) -> Result<Vec<u8>, String> {
+        let image = reqwest::get(url)
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| format!("download preview: {}", e))?
+            .bytes()
+            .await
+            .map_err(|e| format!("download preview: {}", e))?;
+        let cache = self.clone();
+        let save_hash = hash.clone();
+        spawn_blocking(move || cache.save(&save_hash, &image)).await?;
+        self.get(hash).ok_or_else(|| "thumbnail missing after saving".to_string())
+    }
+
+    /// Removes thumbnails of addons that no longer exist
+    pub fn prune(&self, known: &HashSet<String>) {
+        let Ok(dir) = std::fs::read_dir(self.folder()) else { return };
+        let mut removed = 0;
+        for entry in dir.flatten() {
+            let path = entry.path();
+            let Some(stem) = path.file_stem().map(|stem| stem.to_string_lossy().to_string()) else { continue };
+            if !known.contains(&stem) && std::fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            debug!("removed {} unused thumbnails", removed);
+        }
+    }
+}
+
+/// Finds the image of an addon, either next to the vpk with the same name (such as the workshop folder's {id}.jpg),
+/// or an addonimage.jpg, .png, or .vtf inside the vpk
+pub fn find_addon_image(vpk_path: &PathBuf, vpk: Option<&VpkDirectory>) -> Option<Vec<u8>> {
+    for ext in SIDE_IMAGE_EXTENSIONS {
+        let path = vpk_path.with_extension(ext);
+        if path.metadata().is_ok_and(|meta| meta.is_file() && meta.len() <= MAX_IMAGE_SIZE) {
+            if let Ok(image) = std::fs::read(&path) {
+                return Some(image);
+            }
+        }
+    }
+    let vpk = vpk?;
+    VPK_IMAGE_NAMES
+        .iter()
+        .filter_map(|name| vpk.find(name))
+        .find(|file| file.size <= MAX_IMAGE_SIZE)
+        .and_then(|file| vpk.read(file).ok())
+}
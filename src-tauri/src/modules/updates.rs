@@ -0,0 +1,264 @@
+use crate::modules::addons::ItemResult;
+use crate::modules::store::{AddonStorageContainer, FileHash};
+use crate::modules::thumbnails::ThumbnailCache;
+use crate::scan::worker::{async_store_outside_scan, scan_workshop_thread};
+use l4d2_addon_parser::L4D2Addon;
+use log::{debug, info, warn};
+use serde::Serialize;
+use sqlx::__rt::spawn_blocking;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+/// Where updated addon files are downloaded from
+pub trait DownloadSource {
+    /// Downloads url, writing its contents into file at dest
+    fn download(&self, url: &str, dest: &PathBuf) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+/// Downloads over http, from the file_url steam gives
+pub struct HttpSource {
+    client: reqwest::Client,
+}
+impl HttpSource {
+    pub fn new() -> Self {
+        HttpSource {
+            client: reqwest::Client::new(),
+        }
+    }
+}
+impl DownloadSource for HttpSource {
+    async fn download(&self, url: &str, dest: &PathBuf) -> Result<(), String> {
+        let mut response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("download: {}", e))?;
+        let mut file = std::fs::File::create(dest).map_err(|e| format!("create file: {}", e))?;
+        while let Some(chunk) = response.chunk().await.map_err(|e| format!("download: {}", e))? {
+            file.write_all(&chunk).map_err(|e| format!("write file: {}", e))?;
+        }
+        file.sync_all().map_err(|e| format!("write file: {}", e))
+    }
+}
+
+#[derive(Serialize, Debug)]
+/// A managed addon whose workshop item has been updated after the addon's file was last changed
+pub struct OutdatedAddon {
+    /// Entry id (hash) of addon
+    pub id: String,
+    pub filename: String,
+    pub title: String,
+    pub workshop_id: i64,
+    /// When addon's file was last modified, unix seconds
+    pub file_updated: i64,
+    /// When workshop item was last updated, unix seconds
+    pub workshop_updated: i64,
+}
+
+fn get_mtime_secs(path: &PathBuf) -> Option<i64> {
+    let modified = path.metadata().ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
+}
+
+/// Fetches the latest workshop info of every managed addon linked to a workshop item,
+/// and returns the addons whose file is older than the workshop item's last update
+pub async fn check_updates(addons: AddonStorageContainer, addons_folder: &PathBuf) -> Result<Vec<OutdatedAddon>, String> {
+    let linked = {
+        let addons = addons.read().await;
+        addons.list_workshop_linked().await.map_err(|e| e.to_string())?
+    };
+    let mut ids: Vec<i64> = linked.iter().map(|(_, _, _, id)| *id).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    debug!("checking {} workshop items for updates", ids.len());
+    let items = spawn_blocking(move || scan_workshop_thread(ids)).await;
+    let time_updated: HashMap<i64, i64> = items
+        .iter()
+        .filter_map(|item| Some((item.publishedfileid.parse::<i64>().ok()?, item.time_updated as i64)))
+        .collect();
+    {
+        // Keep stored info up to date, update needs the latest file_url
+        let addons = addons.read().await;
+        addons.add_workshop_items(items).await.map_err(|e| e.to_string())?;
+    }
+
+    let outdated: Vec<OutdatedAddon> = linked
+        .into_iter()
+        .filter_map(|(hash, filename, title, workshop_id)| {
+            let workshop_updated = *time_updated.get(&workshop_id)?;
+            let file_updated = get_mtime_secs(&addons_folder.join(&filename))?;
+            (workshop_updated > file_updated).then(|| OutdatedAddon {
+                id: hash.to_string(),
+                filename,
+                title,
+                workshop_id,
+                file_updated,
+                workshop_updated,
+            })
+        })
+        .collect();
+    info!("{} addons have a newer version on workshop", outdated.len());
+    Ok(outdated)
+}
+
+/// Checks the downloaded file is a valid addon before it replaces anything
+fn verify_addon(path: &PathBuf) -> Result<(), String> {
+    let mut addon = L4D2Addon::from_path(path).map_err(|e| format!("downloaded file is not a valid vpk: {}", e))?;
+    addon
+        .info()
+        .map_err(|e| format!("downloaded file has bad addoninfo.txt: {}", e))?
+        .ok_or_else(|| "downloaded file has no addoninfo.txt".to_string())?;
+    Ok(())
+}
+
+/// Downloads the latest version of a managed addon from its workshop item, replacing its file.
+/// Its entry is updated in place, so tags and profiles stay attached
+async fn update_addon(
+    addons: &AddonStorageContainer,
+    addons_folder: &PathBuf,
+    thumbnails: &ThumbnailCache,
+    source: &impl DownloadSource,
+    hash: &FileHash,
+) -> Result<String, String> {
+    let (filename, file_url) = {
+        let addons = addons.read().await;
+        addons
+            .get_workshop_file_url(hash)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "addon is missing, or not linked to a workshop item".to_string())?
+    };
+    if file_url.is_empty() {
+        return Err(format!("{}: workshop item has no download url", filename));
+    }
+    let dest = addons_folder.join(&filename);
+    // Download next to the file, so it can be renamed over it
+    let tmp_dest = addons_folder.join(format!("{}.download", filename));
+    debug!("downloading {} -> {}", file_url, tmp_dest.display());
+    let result = async {
+        source.download(&file_url, &tmp_dest).await?;
+        let path = tmp_dest.clone();
+        spawn_blocking(move || verify_addon(&path)).await?;
+        std::fs::rename(&tmp_dest, &dest).map_err(|e| format!("replace file: {}", e))
+    }
+    .await;
+    if let Err(e) = result {
+        std::fs::remove_file(&tmp_dest).ok();
+        return Err(format!("{}: {}", filename, e));
+    }
+
+    // Entry is found by filename, and takes the new hash
+    async_store_outside_scan(dest, addons, thumbnails, "update")
+        .await
+        .map_err(|e| format!("{}: {}", filename, e))?;
+    info!("Updated {} from workshop", filename);
+    Ok(filename)
+}
+
+/// Updates the given managed addons (by entry id) from their workshop item
+pub async fn update_addons(
+    addons: AddonStorageContainer,
+    addons_folder: &PathBuf,
+    thumbnails: &ThumbnailCache,
+    source: &impl DownloadSource,
+    ids: Vec<String>,
+) -> Vec<ItemResult> {
+    let mut results = Vec::with_capacity(ids.len());
+    for id in ids {
+        let hash = match FileHash::from_str(&id) {
+            Ok(hash) => hash,
+            Err(e) => {
+                results.push(ItemResult::error(id, format!("bad id: {}", e)));
+                continue;
+            }
+        };
+        match update_addon(&addons, addons_folder, thumbnails, source, &hash).await {
+            Ok(filename) => results.push(ItemResult::ok(filename)),
+            Err(e) => {
+                warn!("update: {}", e);
+                results.push(ItemResult::error(id, e));
+            }
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::store::AddonStorage;
+    use crate::scan::worker::{async_process_file, scan_file};
+    use crate::util::test_util::{addon_vpk, serve_once, temp_dir};
+    use sqlx::SqlitePool;
+    use std::sync::Arc;
+    use crate::util::runtime::block_on;
+    use tokio::sync::RwLock;
+
+    #[test]
+    fn update_replaces_file_and_keeps_tags() {
+        let dir = temp_dir("update");
+        let addons_folder = dir.join("addons");
+        let store_folder = dir.join("store");
+        std::fs::create_dir_all(&addons_folder).unwrap();
+        let path = addons_folder.join("test.vpk");
+        std::fs::write(&path, addon_vpk("Test Addon", "1.0")).unwrap();
+        let new_vpk = addon_vpk("Test Addon", "2.0");
+        let url = serve_once(new_vpk.clone());
+
+        block_on(async {
+            let storage = AddonStorage::new(store_folder.clone()).await.unwrap();
+            storage.run_migrations().await.unwrap();
+            let addons = Arc::new(RwLock::new(storage));
+            let thumbnails = ThumbnailCache::new(&store_folder);
+            async_process_file(scan_file(path.clone(), Some(&thumbnails)).unwrap(), addons.clone(), 1).await.unwrap();
+            let (old_hash, _) = addons.read().await.list_hash_filenames().await.unwrap().remove(0);
+            addons.read().await.add_tag(old_hash.clone(), "favorite".to_string()).await.unwrap();
+
+            // Link addon to a workshop item that downloads from the local server
+            let db = SqlitePool::connect(&format!("sqlite://{}", store_folder.join("addon-manager.db").display()))
+                .await
+                .unwrap();
+            sqlx::query("UPDATE addons SET workshop_id = 1 WHERE file_hash = ?")
+                .bind(old_hash.clone())
+                .execute(&db)
+                .await
+                .unwrap();
+            sqlx::query(
+                "INSERT INTO workshop_items (publishedfileid, title, time_created, time_updated, file_size, description, file_url, creator_id, tags) \
+                VALUES (1, 'Test Addon', 0, 0, ?, '', ?, '', '')"
+            )
+                .bind(new_vpk.len() as i64)
+                .bind(&url)
+                .execute(&db)
+                .await
+                .unwrap();
+
+            let results = update_addons(addons.clone(), &addons_folder, &thumbnails, &HttpSource::new(), vec![old_hash.to_string()]).await;
+            assert!(matches!(results.as_slice(), [ItemResult::Ok { filename }] if filename == "test.vpk"));
+            assert_eq!(std::fs::read(&path).unwrap(), new_vpk);
+            assert!(!addons_folder.join("test.vpk.download").exists());
+
+            // Same entry, with the new file's hash and its tags kept
+            let entries = addons.read().await.list_hash_filenames().await.unwrap();
+            assert_eq!(entries.len(), 1);
+            let (new_hash, filename) = &entries[0];
+            assert_ne!(new_hash, &old_hash);
+            assert_eq!(filename.as_deref(), Some("test.vpk"));
+            let tags: Vec<String> = sqlx::query_scalar("select tag from addon_tags where hash = ?")
+                .bind(new_hash.clone())
+                .fetch_all(&db)
+                .await
+                .unwrap();
+            assert_eq!(tags, vec!["favorite".to_string()]);
+
+            db.close().await;
+            addons.read().await.close().await;
+        });
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,214 @@
+use serde::Serialize;
+use sourcepak::common::format::PakReader;
+use sourcepak::pak::v1::format::VPKVersion1;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+/// Archive index of files stored in the directory file itself, which is how addon vpks store everything
+const DIR_ARCHIVE_INDEX: u16 = 0x7FFF;
+/// Size of the version 1 header: signature, version, tree size
+const HEADER_SIZE: u64 = 12;
+
+#[derive(Serialize, Clone, Debug)]
+/// A file stored inside a vpk
+pub struct VpkFile {
+    /// Full path, such as "models/survivors/survivor_gambler.mdl"
+    pub path: String,
+    /// Size in bytes, including any preload data
+    pub size: u64,
+    pub crc: u32,
+}
+
+/// Directory tree of a vpk, only single file vpks (all data in the _dir file) can be read from
+pub struct VpkDirectory {
+    path: PathBuf,
+    /// Offset of the file data section
+    data_offset: u64,
+    vpk: VPKVersion1,
+    pub files: Vec<VpkFile>,
+}
+
+impl VpkDirectory {
+    /// Reads the directory tree of vpk, file data is only read by read()
+    pub fn open(path: &PathBuf) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|e| format!("open vpk: {}", e))?;
+        let mut header = [0u8; HEADER_SIZE as usize];
+        file.read_exact(&mut header).map_err(|e| format!("read header: {}", e))?;
+        let tree_size = u32::from_le_bytes(header[8..12].try_into().unwrap()) as u64;
+        file.rewind().map_err(|e| format!("read header: {}", e))?;
+        let vpk = VPKVersion1::from_file(&mut file).map_err(|e| format!("read tree: {}", e))?;
+
+        let mut files: Vec<VpkFile> = vpk
+            .tree
+            .files
+            .iter()
+            .map(|(path, entry)| VpkFile {
+                path: path.clone(),
+                size: entry.preload_length as u64 + entry.entry_length as u64,
+                crc: entry.crc,
+            })
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(VpkDirectory {
+            path: path.clone(),
+            data_offset: HEADER_SIZE + tree_size,
+            vpk,
+            files,
+        })
+    }
+
+    /// Finds file by its full path, case insensitive
+    pub fn find(&self, path: &str) -> Option<&VpkFile> {
+        self.files.iter().find(|file| file.path.eq_ignore_ascii_case(path))
+    }
+
+    /// Reads the full contents of a file in vpk
+    pub fn read(&self, file: &VpkFile) -> Result<Vec<u8>, String> {
+        let entry = self
+            .vpk
+            .tree
+            .files
+            .get(&file.path)
+            .ok_or_else(|| format!("{} does not exist in vpk", file.path))?;
+        if entry.entry_length > 0 && entry.archive_index != DIR_ARCHIVE_INDEX {
+            return Err(format!("{} is stored in a separate archive, which is not supported", file.path));
+        }
+        let mut data = Vec::with_capacity(file.size as usize);
+        if entry.preload_length > 0 {
+            let preload = self
+                .vpk
+                .tree
+                .preload
+                .get(&file.path)
+                .ok_or_else(|| format!("read {}: missing preload data", file.path))?;
+            data.extend_from_slice(preload);
+        }
+        if entry.entry_length > 0 {
+            let mut vpk = File::open(&self.path).map_err(|e| format!("open vpk: {}", e))?;
+            vpk.seek(SeekFrom::Start(self.data_offset + entry.entry_offset as u64))
+                .and_then(|_| vpk.take(entry.entry_length as u64).read_to_end(&mut data))
+                .map_err(|e| format!("read {}: {}", file.path, e))?;
+        }
+        if data.len() as u64 != file.size {
+            return Err(format!("read {}: unexpected end of file", file.path));
+        }
+        Ok(data)
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum VpkTreeNode {
+    Dir {
+        name: String,
+        path: String,
+        /// Total size of all files in directory
+        size: u64,
+        children: Vec<VpkTreeNode>,
+    },
+    File {
+        name: String,
+        #[serde(flatten)]
+        file: VpkFile,
+    },
+}
+
+impl VpkTreeNode {
+    fn insert(children: &mut Vec<VpkTreeNode>, parent: &str, parts: &[&str], file: &VpkFile) {
+        let [name, rest @ ..] = parts else { return };
+        if rest.is_empty() {
+            children.push(VpkTreeNode::File { name: name.to_string(), file: file.clone() });
+            return;
+        }
+        let path = if parent.is_empty() { name.to_string() } else { format!("{}/{}", parent, name) };
+        let index = match children
+            .iter()
+            .position(|node| matches!(node, VpkTreeNode::Dir { name: dir, .. } if dir == name))
+        {
+            Some(index) => index,
+            None => {
+                children.push(VpkTreeNode::Dir { name: name.to_string(), path: path.clone(), size: 0, children: Vec::new() });
+                children.len() - 1
+            }
+        };
+        if let VpkTreeNode::Dir { size, children, .. } = &mut children[index] {
+            *size += file.size;
+            Self::insert(children, &path, rest, file);
+        }
+    }
+
+    /// Directories first, then by name
+    fn sort(children: &mut Vec<VpkTreeNode>) {
+        children.sort_by(|a, b| {
+            let is_file = |node: &VpkTreeNode| matches!(node, VpkTreeNode::File { .. });
+            let name = |node: &VpkTreeNode| match node {
+                VpkTreeNode::Dir { name, .. } | VpkTreeNode::File { name, .. } => name.to_lowercase(),
+            };
+            (is_file(a), name(a)).cmp(&(is_file(b), name(b)))
+        });
+        for node in children {
+            if let VpkTreeNode::Dir { children, .. } = node {
+                Self::sort(children);
+            }
+        }
+    }
+}
+
+/// Checks path is relative and does not leave the folder it is joined to
+pub fn is_safe_path(path: &str) -> bool {
+    !path.starts_with('/') && path.split(['/', '\\']).all(|part| !part.is_empty() && part != ".." && !part.contains(':'))
+}
+
+/// Checks if file is path, or is inside of directory path. Case insensitive, as paths are in game
+fn is_in_path(file_path: &str, path: &str) -> bool {
+    if path.is_empty() {
+        return true;
+    }
+    file_path.get(..path.len()).is_some_and(|start| start.eq_ignore_ascii_case(path))
+        && (file_path.len() == path.len() || file_path.as_bytes()[path.len()] == b'/')
+}
+
+impl VpkDirectory {
+    /// Returns files grouped into their directories
+    pub fn tree(&self) -> Vec<VpkTreeNode> {
+        let mut root = Vec::new();
+        for file in &self.files {
+            let parts: Vec<&str> = file.path.split('/').collect();
+            VpkTreeNode::insert(&mut root, "", &parts, file);
+        }
+        VpkTreeNode::sort(&mut root);
+        root
+    }
+
+    /// Extracts a file, or every file in a directory, into dest. Paths are kept relative to the parent of path,
+    /// so extracting "scripts/vscripts" creates dest/vscripts/... An empty path extracts all files.
+    /// Returns number of files extracted
+    pub fn extract(&self, path: &str, dest: &PathBuf) -> Result<u32, String> {
+        let path = path.trim_matches('/');
+        let prefix = path.rsplit_once('/').map(|(parent, _)| parent.len() + 1).unwrap_or(0);
+        let files: Vec<&VpkFile> = self
+            .files
+            .iter()
+            .filter(|file| is_in_path(&file.path, path))
+            .collect();
+        if files.is_empty() {
+            return Err(format!("\"{}\" does not exist in addon", path));
+        }
+        let mut extracted = 0;
+        for file in files {
+            let relative = &file.path[prefix..];
+            if !is_safe_path(relative) {
+                return Err(format!("refusing to extract unsafe path \"{}\"", file.path));
+            }
+            let out_path = dest.join(relative);
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| format!("create {}: {}", parent.display(), e))?;
+            }
+            let data = self.read(file)?;
+            std::fs::write(&out_path, data).map_err(|e| format!("write {}: {}", out_path.display(), e))?;
+            extracted += 1;
+        }
+        Ok(extracted)
+    }
+}
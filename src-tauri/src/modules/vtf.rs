@@ -0,0 +1,224 @@
+use image::{DynamicImage, RgbaImage};
+
+const VTF_SIGNATURE: &[u8; 4] = b"VTF\0";
+/// Resource tag of the high resolution image data, used from version 7.3
+const HIGH_RES_RESOURCE_TAG: [u8; 3] = [0x30, 0, 0];
+/// Cube maps store 6 faces per frame, addon images never are one
+const TEXTUREFLAGS_ENVMAP: u32 = 0x4000;
+
+const IMAGE_FORMAT_RGBA8888: i32 = 0;
+const IMAGE_FORMAT_BGRA8888: i32 = 12;
+const IMAGE_FORMAT_DXT1: i32 = 13;
+const IMAGE_FORMAT_DXT5: i32 = 15;
+const IMAGE_FORMAT_DXT1_ONEBITALPHA: i32 = 20;
+
+/// Checks if data starts with the vtf signature
+pub fn is_vtf(data: &[u8]) -> bool {
+    data.starts_with(VTF_SIGNATURE)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| "unexpected end of file".to_string())
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| "unexpected end of file".to_string())
+}
+
+/// Size in bytes of an image in format, None if format is not supported
+fn image_size(format: i32, width: u32, height: u32) -> Option<usize> {
+    let blocks = (width.div_ceil(4) * height.div_ceil(4)) as usize;
+    match format {
+        IMAGE_FORMAT_DXT1 | IMAGE_FORMAT_DXT1_ONEBITALPHA => Some(blocks * 8),
+        IMAGE_FORMAT_DXT5 => Some(blocks * 16),
+        IMAGE_FORMAT_RGBA8888 | IMAGE_FORMAT_BGRA8888 => Some((width * height * 4) as usize),
+        _ => None,
+    }
+}
+
+/// Decodes the first frame of the largest mipmap of a vtf texture
+pub fn decode(data: &[u8]) -> Result<DynamicImage, String> {
+    if !is_vtf(data) {
+        return Err("not a vtf file".to_string());
+    }
+    let minor_version = u32_at(data, 8)?;
+    let header_size = u32_at(data, 12)? as usize;
+    let width = u16_at(data, 16)? as u32;
+    let height = u16_at(data, 18)? as u32;
+    let flags = u32_at(data, 20)?;
+    let frames = u16_at(data, 24)?.max(1) as usize;
+    let format = u32_at(data, 52)? as i32;
+    let mipmaps = *data.get(56).ok_or("unexpected end of file")? as u32;
+    let low_res_format = u32_at(data, 57)? as i32;
+    let low_res_width = *data.get(61).ok_or("unexpected end of file")? as u32;
+    let low_res_height = *data.get(62).ok_or("unexpected end of file")? as u32;
+    let depth = match minor_version >= 2 {
+        true => u16_at(data, 63)?.max(1) as usize,
+        false => 1,
+    };
+    if width == 0 || height == 0 {
+        return Err("image has no size".to_string());
+    }
+    if flags & TEXTUREFLAGS_ENVMAP != 0 {
+        return Err("cube maps are not supported".to_string());
+    }
+    let size = |width: u32, height: u32| {
+        image_size(format, width, height).ok_or_else(|| format!("unsupported image format {}", format))
+    };
+
+    let data_start = match minor_version >= 3 {
+        // Resources follow the header at 80, each a 3 byte tag, flags, and offset
+        true => {
+            let count = u32_at(data, 68)? as usize;
+            let entry = (0..count)
+                .map(|i| 80 + i * 8)
+                .find(|&entry| data.get(entry..entry + 3) == Some(&HIGH_RES_RESOURCE_TAG[..]))
+                .ok_or("missing image data")?;
+            u32_at(data, entry + 4)? as usize
+        }
+        // Low resolution thumbnail comes first, then the image
+        false => {
+            let low_res_size = match low_res_width * low_res_height {
+                0 => 0,
+                _ => image_size(low_res_format, low_res_width, low_res_height)
+                    .ok_or_else(|| format!("unsupported thumbnail format {}", low_res_format))?,
+            };
+            header_size + low_res_size
+        }
+    };
+    // Mipmaps are stored smallest first, so the full size image is last
+    let mut offset = data_start;
+    for level in (1..mipmaps.max(1)).rev() {
+        offset += size((width >> level).max(1), (height >> level).max(1))? * frames * depth;
+    }
+    let image = data
+        .get(offset..offset + size(width, height)?)
+        .ok_or("unexpected end of file")?;
+
+    let pixels = match format {
+        IMAGE_FORMAT_RGBA8888 => image.to_vec(),
+        IMAGE_FORMAT_BGRA8888 => image.chunks_exact(4).flat_map(|px| [px[2], px[1], px[0], px[3]]).collect(),
+        IMAGE_FORMAT_DXT5 => decode_dxt(image, width, height, true),
+        _ => decode_dxt(image, width, height, false),
+    };
+    RgbaImage::from_raw(width, height, pixels)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "bad image data".to_string())
+}
+
+/// Expands a 5:6:5 color to rgb
+fn rgb565(color: u16) -> [u8; 3] {
+    let r = (color >> 11) & 0x1F;
+    let g = (color >> 5) & 0x3F;
+    let b = color & 0x1F;
+    [(r * 255 / 31) as u8, (g * 255 / 63) as u8, (b * 255 / 31) as u8]
+}
+
+/// Decodes DXT1 (8 byte blocks) or DXT5 (16 byte blocks, alpha first) data into rgba pixels
+fn decode_dxt(data: &[u8], width: u32, height: u32, dxt5: bool) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let block_size = if dxt5 { 16 } else { 8 };
+    let blocks_wide = width.div_ceil(4);
+    let mut pixels = vec![0u8; width * height * 4];
+    for (i, block) in data.chunks_exact(block_size).enumerate() {
+        let (bx, by) = ((i % blocks_wide) * 4, (i / blocks_wide) * 4);
+        let (alpha, color) = block.split_at(block_size - 8);
+
+        let c0 = u16::from_le_bytes([color[0], color[1]]);
+        let c1 = u16::from_le_bytes([color[2], color[3]]);
+        let (rgb0, rgb1) = (rgb565(c0), rgb565(c1));
+        let mix = |a: u8, b: u8, wa: u16, wb: u16| ((a as u16 * wa + b as u16 * wb) / (wa + wb)) as u8;
+        let mut colors = [[0u8; 4]; 4];
+        colors[0] = [rgb0[0], rgb0[1], rgb0[2], 255];
+        colors[1] = [rgb1[0], rgb1[1], rgb1[2], 255];
+        if c0 > c1 || dxt5 {
+            colors[2] = [mix(rgb0[0], rgb1[0], 2, 1), mix(rgb0[1], rgb1[1], 2, 1), mix(rgb0[2], rgb1[2], 2, 1), 255];
+            colors[3] = [mix(rgb0[0], rgb1[0], 1, 2), mix(rgb0[1], rgb1[1], 1, 2), mix(rgb0[2], rgb1[2], 1, 2), 255];
+        } else {
+            colors[2] = [mix(rgb0[0], rgb1[0], 1, 1), mix(rgb0[1], rgb1[1], 1, 1), mix(rgb0[2], rgb1[2], 1, 1), 255];
+            colors[3] = [0, 0, 0, 0];
+        }
+        let color_indices = u32::from_le_bytes([color[4], color[5], color[6], color[7]]);
+
+        let mut alphas = [255u8; 8];
+        let mut alpha_indices = 0u64;
+        if dxt5 {
+            let (a0, a1) = (alpha[0] as u16, alpha[1] as u16);
+            alphas[0] = a0 as u8;
+            alphas[1] = a1 as u8;
+            for j in 1..7u16 {
+                alphas[j as usize + 1] = match a0 > a1 {
+                    true => ((a0 * (7 - j) + a1 * j) / 7) as u8,
+                    false if j < 5 => ((a0 * (5 - j) + a1 * j) / 5) as u8,
+                    false if j == 5 => 0,
+                    false => 255,
+                };
+            }
+            alpha_indices = alpha[2..8].iter().rev().fold(0u64, |bits, byte| (bits << 8) | *byte as u64);
+        }
+
+        for py in 0..4 {
+            for px in 0..4 {
+                let (x, y) = (bx + px, by + py);
+                if x >= width || y >= height {
+                    continue;
+                }
+                let index = py * 4 + px;
+                let mut rgba = colors[((color_indices >> (index * 2)) & 0b11) as usize];
+                if dxt5 {
+                    rgba[3] = alphas[((alpha_indices >> (index * 3)) & 0b111) as usize];
+                }
+                let out = (y * width + x) * 4;
+                pixels[out..out + 4].copy_from_slice(&rgba);
+            }
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a version 7.2 vtf with no mipmaps or thumbnail
+    fn build_vtf(format: i32, width: u16, height: u16, image: &[u8]) -> Vec<u8> {
+        let mut vtf = vec![0u8; 80];
+        vtf[..4].copy_from_slice(VTF_SIGNATURE);
+        vtf[4..8].copy_from_slice(&7u32.to_le_bytes());
+        vtf[8..12].copy_from_slice(&2u32.to_le_bytes());
+        vtf[12..16].copy_from_slice(&80u32.to_le_bytes());
+        vtf[16..18].copy_from_slice(&width.to_le_bytes());
+        vtf[18..20].copy_from_slice(&height.to_le_bytes());
+        vtf[24..26].copy_from_slice(&1u16.to_le_bytes());
+        vtf[52..56].copy_from_slice(&format.to_le_bytes());
+        vtf[56] = 1;
+        vtf[57..61].copy_from_slice(&(-1i32).to_le_bytes());
+        vtf[63..65].copy_from_slice(&1u16.to_le_bytes());
+        vtf.extend(image);
+        vtf
+    }
+
+    #[test]
+    fn decodes_bgra() {
+        let vtf = build_vtf(IMAGE_FORMAT_BGRA8888, 1, 2, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let image = decode(&vtf).unwrap().to_rgba8();
+        assert_eq!(image.into_raw(), vec![3, 2, 1, 4, 7, 6, 5, 8]);
+    }
+
+    #[test]
+    fn decodes_dxt1() {
+        // Red and blue endpoints, top row red, rest blue
+        let mut block = Vec::new();
+        block.extend(0xF800u16.to_le_bytes());
+        block.extend(0x001Fu16.to_le_bytes());
+        block.extend(0x55555500u32.to_le_bytes());
+        let vtf = build_vtf(IMAGE_FORMAT_DXT1, 4, 4, &block);
+        let image = decode(&vtf).unwrap().to_rgba8();
+        assert_eq!(image.get_pixel(3, 0).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(0, 3).0, [0, 0, 255, 255]);
+    }
+}
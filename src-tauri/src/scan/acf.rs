@@ -0,0 +1,222 @@
+use log::debug;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// A KeyValues (vdf) value, either a string or a section of more key values
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyValue {
+    Value(String),
+    Section(Vec<(String, KeyValue)>),
+}
+
+impl KeyValue {
+    /// Returns value of key in section, case insensitive like Steam
+    pub fn get(&self, key: &str) -> Option<&KeyValue> {
+        match self {
+            KeyValue::Section(entries) => entries
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            KeyValue::Value(_) => None,
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            KeyValue::Value(value) => Some(value),
+            KeyValue::Section(_) => None,
+        }
+    }
+
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get_str(key)?.parse().ok()
+    }
+
+    /// Returns entries of section, empty if a string
+    pub fn entries(&self) -> &[(String, KeyValue)] {
+        match self {
+            KeyValue::Section(entries) => entries,
+            KeyValue::Value(_) => &[],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    String(String),
+    Open,
+    Close,
+}
+
+/// Splits KeyValues text into tokens, skipping comments and conditionals ([$WIN32])
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(c) => value.push(c),
+                            None => return Err("unexpected end of file in string".to_string()),
+                        },
+                        Some(c) => value.push(c),
+                        None => return Err("unexpected end of file in string".to_string()),
+                    }
+                }
+                tokens.push(Token::String(value));
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            '[' => {
+                while chars.next_if(|c| *c != ']').is_some() {}
+                chars.next();
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                // Unquoted string, ends at whitespace or a brace
+                let mut value = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, '{' | '}' | '"')) {
+                    value.push(c);
+                }
+                tokens.push(Token::String(value));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_section(tokens: &mut impl Iterator<Item = Token>, nested: bool) -> Result<Vec<(String, KeyValue)>, String> {
+    let mut entries = Vec::new();
+    loop {
+        let key = match tokens.next() {
+            Some(Token::String(key)) => key,
+            Some(Token::Close) if nested => return Ok(entries),
+            None if !nested => return Ok(entries),
+            Some(token) => return Err(format!("expected key, found {:?}", token)),
+            None => return Err("unexpected end of file, missing }".to_string()),
+        };
+        let value = match tokens.next() {
+            Some(Token::String(value)) => KeyValue::Value(value),
+            Some(Token::Open) => KeyValue::Section(parse_section(tokens, true)?),
+            Some(Token::Close) => return Err(format!("missing value for \"{}\"", key)),
+            None => return Err(format!("unexpected end of file, missing value for \"{}\"", key)),
+        };
+        entries.push((key, value));
+    }
+}
+
+/// Parses KeyValues text, such as Steam's .acf and .vdf files. Returns the root section
+pub fn parse_keyvalues(input: &str) -> Result<KeyValue, String> {
+    let mut tokens = tokenize(input)?.into_iter();
+    Ok(KeyValue::Section(parse_section(&mut tokens, false)?))
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionState {
+    /// Downloaded and up to date
+    Installed,
+    /// Subscribed, but not downloaded yet
+    Pending,
+    /// Downloaded file does not match the size Steam recorded, download is in progress or was interrupted
+    Partial,
+    /// Steam has a newer version to download
+    NeedsUpdate,
+}
+
+#[derive(Serialize, Debug)]
+/// A workshop item the user is subscribed to, as Steam last recorded it
+pub struct WorkshopSubscription {
+    pub workshop_id: i64,
+    pub state: SubscriptionState,
+    /// Size of installed version, None if never installed
+    pub size: Option<u64>,
+    /// Size of vpk in workshop folder, None if missing
+    pub file_size: Option<u64>,
+    /// When installed version was updated on workshop, unix seconds
+    pub time_updated: Option<i64>,
+    pub manifest: Option<String>,
+}
+
+/// Returns path of Steam's appworkshop_550.acf, which is in steamapps/workshop/
+/// addons folder is at steamapps/common/Left 4 Dead 2/left4dead2/addons
+pub fn get_acf_path(addons_folder: &PathBuf) -> Option<PathBuf> {
+    let steamapps = addons_folder.ancestors().nth(4)?;
+    Some(steamapps.join("workshop").join("appworkshop_550.acf"))
+}
+
+/// Reads Steam's record of L4D2 workshop subscriptions, does not need network.
+/// Returns empty list if Steam has no record (never subscribed, or game is not a Steam install)
+pub fn read_workshop_subscriptions(addons_folder: &PathBuf) -> Result<Vec<WorkshopSubscription>, String> {
+    let Some(path) = get_acf_path(addons_folder).filter(|path| path.is_file()) else {
+        debug!("no appworkshop_550.acf found for {}", addons_folder.display());
+        return Ok(Vec::new());
+    };
+    let contents = std::fs::read_to_string(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let root = parse_keyvalues(&contents).map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+    let acf = root.get("AppWorkshop").ok_or("appworkshop_550.acf has no AppWorkshop section".to_string())?;
+
+    let empty = KeyValue::Section(Vec::new());
+    let installed: HashMap<i64, &KeyValue> = acf
+        .get("WorkshopItemsInstalled")
+        .unwrap_or(&empty)
+        .entries()
+        .iter()
+        .filter_map(|(id, item)| Some((id.parse::<i64>().ok()?, item)))
+        .collect();
+    let workshop_folder = addons_folder.join("workshop");
+
+    // Subscribed items are listed in details, even before they download
+    let mut subscriptions: Vec<WorkshopSubscription> = acf
+        .get("WorkshopItemDetails")
+        .unwrap_or(&empty)
+        .entries()
+        .iter()
+        .filter_map(|(id, details)| {
+            let workshop_id = id.parse::<i64>().ok()?;
+            let installed = installed.get(&workshop_id);
+            let size = installed.and_then(|item| item.get_i64("size")).map(|size| size as u64);
+            let file_size = workshop_folder
+                .join(format!("{}.vpk", workshop_id))
+                .metadata()
+                .ok()
+                .map(|meta| meta.len());
+            let time_updated = installed.and_then(|item| item.get_i64("timeupdated"));
+            let manifest = installed.and_then(|item| item.get_str("manifest")).map(str::to_string);
+
+            let latest_time_updated = details.get_i64("latest_timeupdated").or(details.get_i64("timeupdated"));
+            let latest_manifest = details.get_str("latest_manifest").or(details.get_str("manifest"));
+            let state = if installed.is_none() || file_size.is_none() {
+                SubscriptionState::Pending
+            } else if size != file_size {
+                SubscriptionState::Partial
+            } else if latest_time_updated > time_updated
+                || latest_manifest.is_some_and(|latest| Some(latest) != manifest.as_deref())
+            {
+                SubscriptionState::NeedsUpdate
+            } else {
+                SubscriptionState::Installed
+            };
+            Some(WorkshopSubscription {
+                workshop_id,
+                state,
+                size,
+                file_size,
+                time_updated,
+                manifest,
+            })
+        })
+        .collect();
+    subscriptions.sort_by_key(|item| item.workshop_id);
+    Ok(subscriptions)
+}
@@ -1,9 +1,11 @@
 // Can guarantee id is 4 digits at minimum.
 // IDs are sequential, L4D2 Workshop came out after the 10000th addon was released
-use crate::modules::store::AddonFlags;
+use crate::modules::store::{AddonFlags, FileStamp};
+use crate::scan::acf::read_workshop_subscriptions;
 use l4d2_addon_parser::{AddonContent, AddonInfo};
 use log::{info, warn};
 use regex::Regex;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::LazyLock;
 
@@ -29,6 +31,46 @@ pub(super) fn get_vpks_in_dir(path: &PathBuf) -> Result<Vec<PathBuf>, String> {
     Ok(list)
 }
 
+/// Checks if file has the same size and modification time as when it was last scanned.
+/// Returns the filename if so, None if file is new, changed, or could not be read
+pub(super) fn get_unchanged_filename(path: &PathBuf, known_files: &HashMap<String, FileStamp>) -> Option<String> {
+    let filename = path.file_name()?.to_string_lossy().to_string();
+    let known = known_files.get(&filename)?;
+    let meta = path.metadata().ok()?;
+    if FileStamp::from_meta(&meta).matches(known) {
+        return Some(filename);
+    }
+    None
+}
+
+/// Returns (workshop id, path) of workshop folder vpks that are new or changed since last scan,
+/// and the number of unchanged ones
+pub(super) fn get_changed_workshop_files(path: &PathBuf, known_files: &HashMap<i64, FileStamp>) -> (Vec<(i64, PathBuf)>, u32) {
+    let list = match get_vpks_in_dir(&path.join("workshop")) {
+        Ok(list) => list,
+        Err(e) => {
+            warn!("failed to scan workshop dir: {}", e);
+            return (Vec::new(), 0);
+        }
+    };
+    let mut unchanged = 0;
+    let changed = list
+        .into_iter()
+        .filter_map(|path| {
+            let id = path.file_stem()?.to_string_lossy().parse::<i64>().ok()?;
+            let is_unchanged = known_files.get(&id).is_some_and(|known| {
+                path.metadata().is_ok_and(|meta| FileStamp::from_meta(&meta).matches(known))
+            });
+            if is_unchanged {
+                unchanged += 1;
+                return None;
+            }
+            Some((id, path))
+        })
+        .collect();
+    (changed, unchanged)
+}
+
 /// Attempts to extract workshop ID from addon url or filename
 pub(super) fn find_workshop_id(filename: &str, addon: &AddonInfo) -> Option<i64> {
     // We try filename first, so the user can overwrite with whatever id and change it easily
@@ -74,7 +116,7 @@ impl Into<AddonFlags> for &AddonContent {
     }
 }
 
-pub(super) fn get_workshop_folder_ws_ids(path: &PathBuf) -> Vec<i64> {
+pub(crate) fn get_workshop_folder_ws_ids(path: &PathBuf) -> Vec<i64> {
     match get_vpks_in_dir(&path.join("workshop")) {
         Ok(list) => list
             .into_iter()
@@ -89,3 +131,14 @@ pub(super) fn get_workshop_folder_ws_ids(path: &PathBuf) -> Vec<i64> {
         }
     }
 }
+
+/// Returns ids of every workshop item Steam has the user subscribed to, including ones not downloaded yet
+pub(crate) fn get_subscribed_ws_ids(path: &PathBuf) -> Vec<i64> {
+    match read_workshop_subscriptions(path) {
+        Ok(list) => list.into_iter().map(|item| item.workshop_id).collect(),
+        Err(e) => {
+            warn!("failed to read workshop subscriptions: {}", e);
+            Vec::new()
+        }
+    }
+}
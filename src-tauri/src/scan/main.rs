@@ -1,71 +1,102 @@
+use crate::models::scan::{ScanCounter, ScanError, ScanStatus, ScanTimings};
 use crate::modules::store::AddonStorageContainer;
-use crate::scan::{ScanState};
-use crate::scan::helpers::{get_vpks_in_dir, get_workshop_folder_ws_ids};
-use crate::scan::worker::{
-    AddonFileData, ProcessResult, WorkerTask, async_process_file, scan_worker_thread,
-    scan_workshop_thread,
-};
-use crate::scan::{ScanCounter, ScanSpeed};
+use crate::modules::thumbnails::ThumbnailCache;
+use crate::modules::store::AddonStorage;
+use crate::scan::throttle::{IoThrottle, lower_io_priority};
+use crate::scan::{ScanOptions, ScanSignal, ScanSpeed, ScanState};
+use crate::scan::helpers::{get_changed_workshop_files, get_unchanged_filename, get_subscribed_ws_ids, get_vpks_in_dir, get_workshop_folder_ws_ids};
+use crate::scan::worker::{AddonFileData, ProcessResult, ScanFileError, WorkerTask, scan_worker_thread, scan_workshop_thread};
+use crate::scan::writer::{WRITE_BATCH_SIZE, recv_batch, write_batch};
 use log::error;
 use log::info;
 use log::trace;
 use log::{debug, warn};
-use rand::random;
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering;
 use std::time::Instant;
-use tauri::AppHandle;
-use tauri::Emitter;
 use crate::util::defs::ProgressPayload;
+use crate::util::events::EventSinkContainer;
+
+/// Most results waiting to be stored, workers wait when it is full
+const RESULT_CHANNEL_SIZE: usize = WRITE_BATCH_SIZE * 2;
 
 /// Main thread that starts and manages thread
 pub(super) async fn scan_main(
+    scan_id: u32,
     path: PathBuf,
-    speed: ScanSpeed,
-    running_signal: Arc<AtomicBool>,
+    options: ScanOptions,
+    signal: Arc<ScanSignal>,
     addons: AddonStorageContainer,
-    app: AppHandle,
+    events: EventSinkContainer,
+    thumbnails: ThumbnailCache,
 ) {
+    let speed = options.speed;
     let threads = speed.threads();
     let mut counter = ScanCounter::default();
-    app.emit("scan_state", ScanState::Started { speed }).ok();
-    let scan_id: u32 = random();
+    let mut timings = ScanTimings::default();
+    // Files that failed to scan or store, recorded in scan history at end of scan
+    let mut errors: Vec<ScanError> = Vec::new();
+    events.emit("scan_state", ScanState::Started { speed });
     info!("===== SCAN STARTED =====");
     info!("speed={} scan_id={}", speed, scan_id);
     info!("========================");
     let now = Instant::now();
 
-    // Fetch addons and start worker threads
-    let scan_tasks: Vec<WorkerTask> = get_vpks_in_dir(&path)
-        .expect("failed to scan dir")
-        .into_iter()
-        .map(|path| WorkerTask::ScanFile(path))
-        .collect();
+    // Scan continues from its stored queue if it has one, such as a scan interrupted by the app closing
+    let queued = {
+        let addons = addons.read().await;
+        addons.list_scan_queue(scan_id).await.unwrap_or_default()
+    };
+    let resumed = !queued.is_empty();
+    let scan_tasks: Vec<WorkerTask> = if resumed {
+        info!("resuming unfinished scan with {} files left", queued.len());
+        queued.into_iter().map(WorkerTask::from_queue_entry).collect()
+    } else {
+        let addons = addons.read().await;
+        if let Err(e) = addons.start_scan_record(scan_id, &speed.to_string()).await {
+            error!("failed to record scan: {}", e);
+        }
+        let scan_tasks = list_scan_tasks(&path, scan_id, options.benchmark, &addons, &mut counter).await;
+        let entries: Vec<(PathBuf, Option<i64>)> = scan_tasks.iter().map(WorkerTask::to_queue_entry).collect();
+        if let Err(e) = addons.set_scan_queue(scan_id, &entries).await {
+            error!("failed to store scan queue: {}", e);
+        }
+        scan_tasks
+    };
     let items_to_scan = scan_tasks.len() as u32;
 
     // Allow aborting early right before we enter the main process loop
-    if !running_signal.load(Ordering::SeqCst) {
+    if !signal.is_running() {
         info!("Got early abort signal (1), ending");
+        record_scan_end(&addons, scan_id, ScanStatus::Aborted, &counter, &errors).await;
         return;
     }
 
-    debug!("scan_main: got {} files to scan", scan_tasks.len());
+    debug!("scan_main: got {} files to scan, {} unchanged", scan_tasks.len(), counter.skipped);
     // queue being empty signals threads to end
     let queue = Arc::new(tokio::sync::Mutex::new(VecDeque::<WorkerTask>::from(
         scan_tasks,
     )));
-    // let (tx, mut rx) = tokio::sync::mpsc::channel::<Result<AddonFileData, String>>(60);
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Result<AddonFileData, String>>();
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Result<AddonFileData, ScanFileError>>(RESULT_CHANNEL_SIZE);
+    // Read limit is shared by all workers
+    let throttle = speed.max_bytes_per_sec().map(|bytes| Arc::new(IoThrottle::new(bytes)));
+    let low_priority = matches!(speed, ScanSpeed::Background);
     debug!("starting {} worker threads", threads);
     for i in 0..threads {
         let tx = tx.clone();
         let queue = queue.clone();
+        let signal = signal.clone();
+        let thumbnails = thumbnails.clone();
+        let throttle = throttle.clone();
         std::thread::Builder::new()
             .name("scan-worker-thread".to_string())
-            .spawn(move || scan_worker_thread(i as u8, tx, queue))
+            .spawn(move || {
+                if low_priority {
+                    lower_io_priority();
+                }
+                scan_worker_thread(i as u8, tx, queue, signal, thumbnails, throttle)
+            })
             .expect("failed to spawn worker thread");
     }
     drop(tx); // we don't use it, need to drop so we don't hang
@@ -73,101 +104,208 @@ pub(super) async fn scan_main(
     // acquiring all workshop ids so we can skip fetching any items we have
     debug!("getting existing workshop ids");
     let existing_ws_ids = {
-        let addons = addons.lock().await;
+        let addons = addons.read().await;
         addons.list_workshop_ids().await.unwrap_or_default()
     };
 
-    // Process results of worker threads
+    // Store results of worker threads in batches, with done files removed from the stored queue alongside
     let mut workshop_ids: Vec<i64> = Vec::new();
+    let mut batch = Vec::with_capacity(WRITE_BATCH_SIZE);
     trace!("starting scan file processing loop");
-    while let Some(result) = rx.recv().await {
-        match result {
-            Ok(file) => {
-                counter.total += 1;
-                match async_process_file(file, addons.clone(), scan_id).await {
-                    Ok((ProcessResult::Added, workshop_id)) => {
-                        counter.added += 1;
-                        if let Some(ws_id) = workshop_id {
-                            if !existing_ws_ids.contains(&ws_id) {
-                                workshop_ids.push(ws_id);
-                            }
+    while recv_batch(&mut rx, &mut batch).await {
+        for file in write_batch(&addons, scan_id, std::mem::take(&mut batch), &mut timings).await {
+            counter.total += 1;
+            match file.result {
+                Ok((ProcessResult::Added, workshop_id)) => {
+                    counter.added += 1;
+                    if let Some(ws_id) = workshop_id {
+                        if !existing_ws_ids.contains(&ws_id) {
+                            workshop_ids.push(ws_id);
                         }
                     }
-                    Ok((ProcessResult::UpdatedByHash, _) | (ProcessResult::UpdatedByFilename, _)) => {
-                        counter.updated += 1;
-                    }
-                    Err(err) => {
-                        counter.errors += 1;
-                        error!("process_file: {}", err);
-                    }
+                }
+                Ok((ProcessResult::UpdatedByHash, _) | (ProcessResult::UpdatedByFilename, _)) => {
+                    counter.updated += 1;
+                }
+                Err(error) => {
+                    counter.errors += 1;
+                    errors.push(ScanError {
+                        scan_id: scan_id as i64,
+                        filename: file.filename,
+                        workshop_id: file.workshop_id,
+                        error,
+                    });
                 }
             }
-            Err(err) => {
-                warn!("scan_file: {}", err);
-            }
-        };
+        }
 
-        app.emit("scan_progress", ProgressPayload::new(counter.total, items_to_scan)).ok();
+        events.emit("scan_progress", ProgressPayload::new(counter.total, items_to_scan));
 
         // Check if we should abort
-        if !running_signal.load(Ordering::SeqCst) {
+        if !signal.is_running() {
             info!("Got abort signal in process loop, ending");
             queue.lock().await.clear(); // drain queue to signal worker threads to end
             while let Some(_) = rx.recv().await {} // wait for all threads to end
+            record_scan_end(&addons, scan_id, ScanStatus::Aborted, &counter, &errors).await;
             return;
         }
     }
     debug!("all addons scanned and processed");
 
-    debug!("resolving workshop folder addons");
-    let workshop_folder_ids = get_workshop_folder_ws_ids(&path);
-    // merge any missing workshop folder ids to queue
-    workshop_ids.extend(
-        workshop_folder_ids
-            .iter()
-            .filter(|id| !existing_ws_ids.contains(id)),
-    );
-    let workshop_items = std::thread::spawn(|| scan_workshop_thread(workshop_ids))
-        .join()
-        .expect("workshop thread panicked");
-
-    let addons = addons.lock().await;
-    debug!("adding {} workshop items", workshop_items.len());
-    addons
-        .add_workshop_items(workshop_items)
-        .await
-        .expect("failed to add workshop items");
-    debug!("marking {} workshop ids", workshop_folder_ids.len());
-    addons
-        .mark_workshop_ids(workshop_folder_ids)
+    sync_workshop_items(&path, workshop_ids, &addons)
         .await
-        .expect("failed to mark workshop ids"); // this should be after add_workshop_items, need items to exist first
+        .expect("failed to sync workshop items");
+
+    let addons = addons.read().await;
     debug!("marking any missing files");
     addons
         .scan_mark_missing(scan_id)
         .await
         .expect("failed to mark missing files");
 
+    debug!("removing unused thumbnails");
+    match addons.list_known_hashes().await {
+        Ok(hashes) => thumbnails.prune(&hashes.iter().map(|hash| hash.to_string()).collect()),
+        Err(e) => warn!("failed to list addons for thumbnails: {}", e),
+    }
+
+    if let Err(e) = addons.finish_scan_record(scan_id, ScanStatus::Complete, &counter, &errors).await {
+        error!("failed to record scan: {}", e);
+    }
+
     info!("all tasks done");
 
-    app.emit(
+    events.emit(
         "scan_state",
         ScanState::Complete {
             time: now.elapsed().as_secs(),
             total: counter.total,
             added: counter.added,
             updated: counter.updated,
+            skipped: counter.skipped,
             failed: counter.errors,
         },
-    )
-    .ok();
+    );
 
     info!("====== SCAN COMPLETE ======");
     info!(
-        "{} addons scanned, {} added, {} updated, {} failed",
-        counter.total, counter.added, counter.updated, counter.errors,
+        "{} addons scanned, {} added, {} updated, {} skipped, {} failed",
+        counter.total, counter.added, counter.updated, counter.skipped, counter.errors,
     );
     info!("Duration: {} seconds", now.elapsed().as_secs());
     info!("===========================");
-    running_signal.store(true, Ordering::SeqCst); // signal that scan over
+    let benchmark = timings.report(now.elapsed());
+    debug!("scan timings: {:?}", benchmark);
+    if options.benchmark {
+        info!(
+            "Benchmark: {} files, {:.1} files/sec, hashing {}ms, parsing {}ms, storing {}ms",
+            benchmark.files, benchmark.files_per_sec, benchmark.hash_ms, benchmark.parse_ms, benchmark.db_ms
+        );
+        events.emit("scan_benchmark", benchmark);
+    }
+    signal.set_running(true); // signal that scan over
+}
+
+/// Lists files to scan in addons folder and its workshop folder. Files unchanged since last scan
+/// are skipped, unless rescan_all is set, and marked as seen so they are not marked missing at end of scan
+async fn list_scan_tasks(
+    path: &PathBuf,
+    scan_id: u32,
+    rescan_all: bool,
+    addons: &AddonStorage,
+    counter: &mut ScanCounter,
+) -> Vec<WorkerTask> {
+    // Files with same size & mtime as last scan are skipped, their stored hash & info is reused
+    let known_files = match rescan_all {
+        true => HashMap::new(),
+        false => addons.list_file_stamps().await.unwrap_or_default(),
+    };
+    let mut unchanged_filenames: Vec<String> = Vec::new();
+
+    let mut scan_tasks: Vec<WorkerTask> = get_vpks_in_dir(path)
+        .expect("failed to scan dir")
+        .into_iter()
+        .filter(|path| match get_unchanged_filename(path, &known_files) {
+            Some(filename) => {
+                unchanged_filenames.push(filename);
+                false
+            }
+            None => true,
+        })
+        .map(|path| WorkerTask::ScanFile(path))
+        .collect();
+
+    // Workshop folder vpks are scanned too, to get their info. Their entries are by workshop id
+    let known_workshop_files = match rescan_all {
+        true => HashMap::new(),
+        false => addons.list_workshop_file_stamps().await.unwrap_or_default(),
+    };
+    let (workshop_files, unchanged_workshop_files) = get_changed_workshop_files(path, &known_workshop_files);
+    scan_tasks.extend(workshop_files.into_iter().map(|(id, path)| WorkerTask::ScanWorkshopFile(id, path)));
+
+    counter.skipped = unchanged_filenames.len() as u32 + unchanged_workshop_files;
+    if let Err(e) = addons.touch_entries(&unchanged_filenames, scan_id).await {
+        error!("failed to mark unchanged files: {}", e);
+    }
+    scan_tasks
+}
+
+/// Records end of scan in scan history
+async fn record_scan_end(
+    addons: &AddonStorageContainer,
+    scan_id: u32,
+    status: ScanStatus,
+    counter: &ScanCounter,
+    errors: &[ScanError],
+) {
+    let addons = addons.read().await;
+    if let Err(e) = addons.finish_scan_record(scan_id, status, counter, errors).await {
+        error!("failed to record scan: {}", e);
+    }
+}
+
+/// Fetches any of the given workshop ids, the ids in the workshop folder, subscribed ids, and ids of addons whose
+/// fetch failed before, that are not in the db yet. Then marks which ids are currently in the workshop folder
+pub(super) async fn sync_workshop_items(
+    path: &PathBuf,
+    mut workshop_ids: Vec<i64>,
+    addons: &AddonStorageContainer,
+) -> Result<(), sqlx::Error> {
+    let existing_ws_ids = {
+        let addons = addons.read().await;
+        // Includes addons added before an interrupted scan, or by the watcher, that have no item yet
+        match addons.list_unfetched_workshop_ids().await {
+            Ok(ids) => workshop_ids.extend(ids),
+            Err(e) => error!("failed to get unfetched workshop ids: {}", e),
+        }
+        addons.list_workshop_ids().await.unwrap_or_default()
+    };
+    debug!("resolving workshop folder addons");
+    let workshop_folder_ids = get_workshop_folder_ws_ids(path);
+    // merge any missing workshop folder ids to queue
+    workshop_ids.extend(workshop_folder_ids.iter());
+    // and subscriptions steam hasn't downloaded yet
+    workshop_ids.extend(get_subscribed_ws_ids(path));
+    workshop_ids.retain(|id| !existing_ws_ids.contains(id));
+    workshop_ids.sort_unstable();
+    workshop_ids.dedup();
+    let fetch_ids = workshop_ids.clone();
+    let workshop_items = tokio::task::spawn_blocking(move || scan_workshop_thread(fetch_ids))
+        .await
+        .expect("workshop thread panicked");
+    let fetched: HashSet<String> = workshop_items.iter().map(|item| item.publishedfileid.clone()).collect();
+    let failed: Vec<i64> = workshop_ids.into_iter().filter(|id| !fetched.contains(&id.to_string())).collect();
+    if !failed.is_empty() {
+        // Still not in the db, so they are tried again next sync
+        warn!("could not fetch {} workshop items: {:?}", failed.len(), failed);
+    }
+
+    let addons = addons.read().await;
+    debug!("adding {} workshop items", workshop_items.len());
+    addons.add_workshop_items(workshop_items).await?;
+    debug!("removing info of workshop files no longer in folder");
+    addons.delete_workshop_files_except(&workshop_folder_ids).await?;
+    debug!("marking {} workshop ids", workshop_folder_ids.len());
+    // this should be after add_workshop_items, need items to exist first
+    addons.mark_workshop_ids(workshop_folder_ids).await
 }
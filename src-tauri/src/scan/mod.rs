@@ -1,34 +1,35 @@
 use crate::modules::store::AddonStorageContainer;
+use crate::modules::thumbnails::ThumbnailCache;
 use crate::scan::main::scan_main;
 use crate::scan::worker::ProcessError;
+use crate::util::events::EventSinkContainer;
 use log::debug;
 use log::info;
+use log::warn;
+use rand::random;
 use serde::{Deserialize, Serialize};
 use sqlx::__rt::timeout;
 use std::fmt::Display;
 use std::path::PathBuf;
-use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
+use std::sync::{Arc, Condvar};
 use std::time::Duration;
-use tauri::AppHandle;
-use tauri::Emitter;
-use tauri::async_runtime::block_on;
 use tokio::sync::Mutex;
 
-mod helpers;
+pub(crate) mod acf;
+pub(crate) mod helpers;
 mod main;
-mod worker;
+#[cfg(feature = "gui")]
+pub mod scheduler;
+#[cfg(feature = "gui")]
+pub mod watcher;
+pub(crate) mod throttle;
+pub(crate) mod worker;
+mod writer;
 
 const SCAN_ABORT_TIMEOUT_SEC: u64 = 60;
 
-#[derive(Default)]
-struct ScanCounter {
-    total: u32,
-    added: u32,
-    updated: u32,
-    errors: u32,
-}
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "state")]
@@ -39,11 +40,16 @@ pub enum ScanState {
     Aborted {
         reason: Option<String>,
     },
+    /// Workers are parked, the scan keeps its queue
+    Paused,
+    Resumed,
     Complete {
         time: u64,
         total: u32,
         added: u32,
         updated: u32,
+        /// Files that were unchanged since last scan, and not rescanned
+        skipped: u32,
         failed: u32,
     },
 }
@@ -56,26 +62,84 @@ impl Display for ProcessError {
                 write!(f, "Error updating existing item: {}", e)
             }
             ProcessError::NewEntryError(e) => write!(f, "Error creating new entry: {}", e),
+            ProcessError::IndexFilesError(e) => write!(f, "Error indexing addon files: {}", e),
+            ProcessError::TransactionError(e) => write!(f, "Error storing changes: {}", e),
+        }
+    }
+}
+
+/// Controls a running scan, shared by the scanner, scan_main and the worker threads
+pub(crate) struct ScanSignal {
+    /// Cleared to abort the scan
+    running: AtomicBool,
+    paused: std::sync::Mutex<bool>,
+    unpaused: Condvar,
+}
+impl ScanSignal {
+    fn new() -> Self {
+        ScanSignal {
+            running: AtomicBool::new(false),
+            paused: std::sync::Mutex::new(false),
+            unpaused: Condvar::new(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn set_running(&self, running: bool) {
+        self.running.store(running, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap()
+    }
+
+    /// Returns false if scan already was in that state
+    pub fn set_paused(&self, paused: bool) -> bool {
+        let mut current = self.paused.lock().unwrap();
+        if *current == paused {
+            return false;
+        }
+        *current = paused;
+        if !paused {
+            self.unpaused.notify_all();
         }
+        true
+    }
+
+    /// Blocks the calling thread while the scan is paused
+    pub fn wait_if_paused(&self) {
+        let paused = self.paused.lock().unwrap();
+        let _unpaused = self.unpaused.wait_while(paused, |paused| *paused).unwrap();
     }
 }
 
 pub struct AddonScanner {
     scan_main_task: Option<tokio::task::JoinHandle<()>>,
-    running_signal: Arc<AtomicBool>,
+    /// Id of the running or last scan
+    scan_id: Option<u32>,
+    signal: Arc<ScanSignal>,
     addons: AddonStorageContainer,
-    app: AppHandle,
+    events: EventSinkContainer,
+    thumbnails: ThumbnailCache,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum ScanSpeed {
     /// Uses all threads
     Maximum,
     /// Uses half of threads
     Normal,
-    /// Uses one thread
+    /// Uses one thread, with lowered I/O priority
     Background,
+    /// Uses the given number of threads, hashing files at up to max_mb_per_sec if set
+    Custom {
+        threads: u8,
+        max_mb_per_sec: Option<u32>,
+    },
 }
 
 impl Display for ScanSpeed {
@@ -85,6 +149,10 @@ impl Display for ScanSpeed {
             ScanSpeed::Maximum => write!(f, "Maximum ({} threads)", threads),
             ScanSpeed::Normal => write!(f, "Normal ({} threads)", threads),
             ScanSpeed::Background => write!(f, "Background ({} threads)", threads),
+            ScanSpeed::Custom { max_mb_per_sec: Some(mb), .. } => {
+                write!(f, "Custom ({} threads, {} MB/s)", threads, mb)
+            }
+            ScanSpeed::Custom { max_mb_per_sec: None, .. } => write!(f, "Custom ({} threads)", threads),
         }
     }
 }
@@ -102,18 +170,36 @@ impl ScanSpeed {
             ScanSpeed::Maximum => num_cpus::get() as u8,
             ScanSpeed::Normal => (num_cpus::get() as f32 / 2.0).ceil() as u8,
             ScanSpeed::Background => 1,
+            ScanSpeed::Custom { threads, .. } => (*threads).max(1),
+        }
+    }
+
+    /// Returns how many bytes per second all threads together may hash, None if unlimited
+    pub fn max_bytes_per_sec(&self) -> Option<u64> {
+        match self {
+            ScanSpeed::Custom { max_mb_per_sec: Some(mb), .. } if *mb > 0 => Some(*mb as u64 * 1024 * 1024),
+            _ => None,
         }
     }
 }
 
+#[derive(Clone, Copy)]
+pub(crate) struct ScanOptions {
+    pub speed: ScanSpeed,
+    /// Rescans every file, even unchanged ones, and reports the scan's timings at end
+    pub benchmark: bool,
+}
+
 pub type ScannerContainer = Mutex<AddonScanner>;
 impl AddonScanner {
-    pub fn new(addons: AddonStorageContainer, app: AppHandle) -> Self {
+    pub fn new(addons: AddonStorageContainer, events: EventSinkContainer, thumbnails: ThumbnailCache) -> Self {
         Self {
             scan_main_task: None,
-            running_signal: Arc::new(AtomicBool::new(false)),
+            scan_id: None,
+            signal: Arc::new(ScanSignal::new()),
             addons,
-            app,
+            events,
+            thumbnails,
         }
     }
 
@@ -123,23 +209,64 @@ impl AddonScanner {
     /// When worker tasks complete, any workshop ids to fetch items are sent, and resolved once we have over 100
     /// When all worker tasks done, any remaining workshop items are fetched in batches of 100
     pub fn start(&mut self, path: PathBuf, speed: ScanSpeed) -> bool {
+        self.spawn(random(), path, ScanOptions { speed, benchmark: false })
+    }
+
+    /// Starts a scan of every file, including unchanged ones, that emits scan_benchmark with files/sec
+    /// and time spent hashing, parsing and storing when complete
+    pub fn benchmark(&mut self, path: PathBuf, speed: ScanSpeed) -> bool {
+        self.spawn(random(), path, ScanOptions { speed, benchmark: true })
+    }
+
+    /// Continues a scan that never ended, such as one interrupted by the app closing, from its stored queue
+    pub fn resume_unfinished(&mut self, scan_id: u32, path: PathBuf, speed: ScanSpeed) -> bool {
+        self.spawn(scan_id, path, ScanOptions { speed, benchmark: false })
+    }
+
+    fn spawn(&mut self, scan_id: u32, path: PathBuf, options: ScanOptions) -> bool {
         if self.check_running() {
             return false;
         } // ignore if not running
 
         let addons = self.addons.clone();
-        let app = self.app.clone();
-        let running_signal = self.running_signal.clone();
-        self.running_signal.store(true, Ordering::SeqCst);
+        let events = self.events.clone();
+        let thumbnails = self.thumbnails.clone();
+        let signal = self.signal.clone();
+        self.scan_id = Some(scan_id);
+        self.signal.set_paused(false);
+        self.signal.set_running(true);
         self.scan_main_task = Some(tokio::spawn(scan_main(
+            scan_id,
             path,
-            speed,
-            running_signal,
+            options,
+            signal,
             addons,
-            app,
+            events,
+            thumbnails,
         )));
         true
     }
+
+    /// Parks the worker threads of the running scan, returns false if not running or already paused
+    pub fn pause(&mut self) -> bool {
+        if !self.check_running() || !self.signal.set_paused(true) {
+            return false;
+        }
+        info!("Scan paused");
+        self.events.emit("scan_state", ScanState::Paused);
+        true
+    }
+
+    /// Unparks the worker threads of a paused scan, returns false if not paused
+    pub fn resume(&mut self) -> bool {
+        if !self.check_running() || !self.signal.set_paused(false) {
+            return false;
+        }
+        info!("Scan resumed");
+        self.events.emit("scan_state", ScanState::Resumed);
+        true
+    }
+
     pub async fn abort(&mut self, mut reason: Option<String>) {
         info!("Aborting scan");
         if !self.check_running() {
@@ -147,7 +274,9 @@ impl AddonScanner {
         } // ignore if not running
 
         // this tells thread to abort, but reusing the same signal does
-        self.running_signal.store(false, Ordering::SeqCst);
+        self.signal.set_running(false);
+        // workers have to run to see the abort
+        self.signal.set_paused(false);
         // wait for thread to end
         // let main_task = self.scan_main_task.take().unwrap();
         let abort_timed = timeout(
@@ -160,9 +289,24 @@ impl AddonScanner {
             reason = reason.map(|reason| format!("{} (timed out)", reason));
         }
         info!("Scan aborted for \"{:?}\"", reason);
-        self.app
-            .emit("scan_state", ScanState::Aborted { reason })
-            .ok();
+        if let (Some(id), Some(reason)) = (self.scan_id, &reason) {
+            if let Err(e) = self.addons.read().await.set_scan_aborted_reason(id, reason).await {
+                warn!("failed to record abort reason: {}", e);
+            }
+        }
+        self.events.emit("scan_state", ScanState::Aborted { reason });
+    }
+
+    /// Waits for the running scan, if any, to finish
+    pub async fn wait(&mut self) {
+        if let Some(task) = self.scan_main_task.take() {
+            task.await.ok();
+        }
+    }
+
+    /// Id of the running or last scan
+    pub fn scan_id(&self) -> Option<u32> {
+        self.scan_id
     }
 
     /// Is a scan running
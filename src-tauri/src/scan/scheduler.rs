@@ -0,0 +1,195 @@
+use crate::models::scan::ScanStatus;
+use crate::modules::cfg::AppConfigContainer;
+use crate::modules::server::get_install_root;
+use crate::modules::store::AddonStorageContainer;
+use crate::scan::{ScanSpeed, ScannerContainer};
+use crate::util::{Notification, NotificationType};
+use chrono::{Local, Timelike, Utc};
+use log::{debug, info, warn};
+use sqlx::__rt::spawn_blocking;
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::time::Duration;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+use tauri::{AppHandle, Manager};
+
+/// How often the scheduler checks if a scan is due, or if a scan it started has ended
+const SCHEDULER_TICK: Duration = Duration::from_secs(30);
+/// Hours between scans when only quiet hours are set, so there is one scan per night
+const QUIET_HOURS_INTERVAL_HOURS: u32 = 12;
+/// Process names of the game when it is not run from its install directly, such as under Proton
+const GAME_PROCESS_NAMES: [&str; 1] = ["left4dead2.exe"];
+/// Set to skip the startup scan, so restarts during development don't scan each time
+const SKIP_STARTUP_SCAN_ENV: &str = "APP_SKIP_STARTUP_SCAN";
+
+/// Starts scans on its own: on startup if enabled or a scan was interrupted, then every scan_interval_hours
+/// within the quiet hours. Scans wait while another scan or the game is running, and a summary is sent
+/// as a notification when they complete
+pub fn start_scheduler(app: AppHandle) {
+    tauri::async_runtime::spawn(scheduler_main(app));
+}
+
+async fn scheduler_main(app: AppHandle) {
+    // Scan the scheduler started, to notify when it ends
+    let mut pending: Option<u32> = None;
+    if std::env::var_os(SKIP_STARTUP_SCAN_ENV).is_some() {
+        info!("{} is set, skipping startup scan", SKIP_STARTUP_SCAN_ENV);
+    } else {
+        pending = startup_scan(&app).await;
+    }
+    loop {
+        sqlx::__rt::sleep(SCHEDULER_TICK).await;
+        if let Some(scan_id) = pending {
+            if app.state::<ScannerContainer>().lock().await.check_running() {
+                continue;
+            }
+            notify_scan_end(&app, scan_id).await;
+            pending = None;
+        }
+        pending = scheduled_scan(&app).await;
+    }
+}
+
+/// Continues a scan interrupted by the app closing, or starts one if startup_scan is set. Returns id of started scan
+async fn startup_scan(app: &AppHandle) -> Option<u32> {
+    let cfg = app.state::<AppConfigContainer>().lock().await.clone();
+    let addons_folder = cfg.addons_folder.clone()?;
+    let unfinished = get_unfinished_scan(app).await;
+    if unfinished.is_none() && !cfg.startup_scan {
+        return None;
+    }
+    if is_game_running(addons_folder.clone()).await {
+        info!("Game is running, skipping startup scan");
+        return None;
+    }
+    info!("Starting startup scan");
+    start_or_resume(app, unfinished, addons_folder, cfg.scheduled_scan_speed).await
+}
+
+/// Returns id of the scan interrupted by the app closing, if there is one
+async fn get_unfinished_scan(app: &AppHandle) -> Option<u32> {
+    let addons = app.state::<AddonStorageContainer>();
+    let addons = addons.read().await;
+    match addons.get_unfinished_scan().await {
+        Ok(scan) => scan.map(|scan| scan.id as u32),
+        Err(e) => {
+            warn!("failed to get unfinished scan: {}", e);
+            None
+        }
+    }
+}
+
+/// Resumes the unfinished scan if there is one, so its progress is kept, otherwise starts a new scan.
+/// Returns id of started scan
+async fn start_or_resume(app: &AppHandle, unfinished: Option<u32>, addons_folder: PathBuf, speed: ScanSpeed) -> Option<u32> {
+    let scanner = app.state::<ScannerContainer>();
+    let mut scanner = scanner.lock().await;
+    let started = match unfinished {
+        Some(scan_id) => {
+            info!("Resuming unfinished scan {}", scan_id);
+            scanner.resume_unfinished(scan_id, addons_folder, speed)
+        }
+        None => scanner.start(addons_folder, speed),
+    };
+    started.then(|| scanner.scan_id()).flatten()
+}
+
+/// Starts a scan if the schedule has one due. Returns id of started scan
+async fn scheduled_scan(app: &AppHandle) -> Option<u32> {
+    let cfg = app.state::<AppConfigContainer>().lock().await.clone();
+    let addons_folder = cfg.addons_folder.clone()?;
+    let quiet_hours = cfg.scan_quiet_start.zip(cfg.scan_quiet_end);
+    let interval_hours = match (cfg.scan_interval_hours, quiet_hours) {
+        (Some(hours), _) => hours,
+        (None, Some(_)) => QUIET_HOURS_INTERVAL_HOURS,
+        (None, None) => return None,
+    };
+    if let Some((start, end)) = quiet_hours {
+        if !in_quiet_hours(Local::now().hour() as u8, start, end) {
+            return None;
+        }
+    }
+
+    let last_started = {
+        let addons = app.state::<AddonStorageContainer>();
+        let addons = addons.read().await;
+        match addons.list_scans(1).await {
+            Ok(scans) => scans.first().map(|scan| scan.started_at),
+            Err(e) => {
+                warn!("failed to get last scan: {}", e);
+                return None;
+            }
+        }
+    };
+    if last_started.is_some_and(|started| Utc::now().timestamp() - started < interval_hours as i64 * 3600) {
+        return None;
+    }
+    if app.state::<ScannerContainer>().lock().await.check_running() {
+        return None;
+    }
+    if is_game_running(addons_folder.clone()).await {
+        debug!("game is running, waiting to start scheduled scan");
+        return None;
+    }
+
+    info!("Starting scheduled scan");
+    let unfinished = get_unfinished_scan(app).await;
+    start_or_resume(app, unfinished, addons_folder, cfg.scheduled_scan_speed).await
+}
+
+/// Sends a summary of a scan that completed. Aborted scans were stopped by the user, and are not notified
+async fn notify_scan_end(app: &AppHandle, scan_id: u32) {
+    let scan = {
+        let addons = app.state::<AddonStorageContainer>();
+        let addons = addons.read().await;
+        addons.get_scan(scan_id).await
+    };
+    let scan = match scan {
+        Ok(Some(scan)) if scan.status == ScanStatus::Complete => scan,
+        Ok(_) => return,
+        Err(e) => {
+            warn!("failed to get scan {}: {}", scan_id, e);
+            return;
+        }
+    };
+    let typ = match scan.failed {
+        0 => NotificationType::Info,
+        _ => NotificationType::Warn,
+    };
+    Notification::new(
+        typ,
+        "Scan complete".to_string(),
+        Some(format!(
+            "{} scanned, {} added, {} updated, {} skipped, {} failed",
+            scan.total, scan.added, scan.updated, scan.skipped, scan.failed
+        )),
+    )
+    .send(app);
+}
+
+/// Checks if hour is from start up to end, wrapping past midnight if end is before start. Same start and end is all day
+fn in_quiet_hours(hour: u8, start: u8, end: u8) -> bool {
+    match start.cmp(&end) {
+        Ordering::Less => hour >= start && hour < end,
+        Ordering::Greater => hour >= start || hour < end,
+        Ordering::Equal => true,
+    }
+}
+
+/// Checks if the game, or a dedicated server, is running from the install of the addons folder
+async fn is_game_running(addons_folder: PathBuf) -> bool {
+    let Some(root) = get_install_root(&addons_folder) else { return false };
+    spawn_blocking(move || {
+        let mut system = System::new();
+        system.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing().with_exe(UpdateKind::OnlyIfNotSet),
+        );
+        system.processes().values().any(|process| {
+            process.exe().is_some_and(|exe| exe.starts_with(&root))
+                || GAME_PROCESS_NAMES.iter().any(|name| process.name().eq_ignore_ascii_case(name))
+        })
+    })
+    .await
+}
@@ -0,0 +1,59 @@
+use log::warn;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Limits how fast worker threads read files, shared between all of them
+pub(crate) struct IoThrottle {
+    bytes_per_sec: u64,
+    /// When the next read is allowed to start
+    next_read: Mutex<Instant>,
+}
+impl IoThrottle {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        IoThrottle {
+            bytes_per_sec: bytes_per_sec.max(1),
+            next_read: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Blocks the calling thread until a read of the given size stays under the limit
+    pub fn wait(&self, bytes: u64) {
+        let wait = {
+            let mut next_read = self.next_read.lock().unwrap();
+            let now = Instant::now();
+            let start = (*next_read).max(now);
+            *next_read = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+            start - now
+        };
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+/// Lowers the I/O priority of the calling thread to idle, so a background scan yields the disk to the game
+#[cfg(target_os = "linux")]
+pub(crate) fn lower_io_priority() {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    // I/O priority is per thread on linux, pid 0 is the calling thread
+    let result = unsafe {
+        libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT)
+    };
+    if result != 0 {
+        warn!("failed to lower io priority: {}", std::io::Error::last_os_error());
+    }
+}
+
+/// Puts the calling thread in background mode, which lowers its I/O priority along with its CPU priority
+#[cfg(windows)]
+pub(crate) fn lower_io_priority() {
+    use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_BEGIN};
+    if unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN) } == 0 {
+        warn!("failed to lower io priority: {}", std::io::Error::last_os_error());
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub(crate) fn lower_io_priority() {}
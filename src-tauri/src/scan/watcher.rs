@@ -0,0 +1,239 @@
+use crate::models::scan::ScanError;
+use crate::modules::store::AddonStorageContainer;
+use crate::modules::thumbnails::ThumbnailCache;
+use crate::scan::main::sync_workshop_items;
+use crate::scan::worker::{ProcessResult, async_process_file, async_process_workshop_file, scan_file};
+use log::{debug, error, info, trace, warn};
+use notify_debouncer_full::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{DebounceEventResult, Debouncer, RecommendedCache, new_debouncer};
+use rand::random;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// How long to wait for file events to settle, steam and file copies write in many chunks
+const WATCH_DEBOUNCE_MS: u64 = 2000;
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchChange {
+    Added,
+    Updated,
+    Removed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+/// Sent to frontend in "addons_changed" event, for each entry that changed
+pub struct WatchedEntry {
+    pub filename: String,
+    /// Is entry from the workshop folder
+    pub workshop: bool,
+    pub change: WatchChange,
+}
+
+pub struct AddonWatcher {
+    debouncer: Option<Debouncer<RecommendedWatcher, RecommendedCache>>,
+    addons: AddonStorageContainer,
+    app: AppHandle,
+}
+
+pub type WatcherContainer = Mutex<AddonWatcher>;
+impl AddonWatcher {
+    pub fn new(addons: AddonStorageContainer, app: AppHandle) -> Self {
+        Self {
+            debouncer: None,
+            addons,
+            app,
+        }
+    }
+
+    /// Starts watching the addons folder and its workshop folder, replacing any existing watch.
+    /// Changed files are debounced, then only those files are scanned and updated in the db
+    pub fn start(&mut self, addons_folder: PathBuf) -> Result<(), String> {
+        self.stop();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<Vec<PathBuf>>();
+        let mut debouncer = new_debouncer(
+            Duration::from_millis(WATCH_DEBOUNCE_MS),
+            None,
+            move |result: DebounceEventResult| match result {
+                Ok(events) => {
+                    let paths: Vec<PathBuf> = events
+                        .into_iter()
+                        .filter(|event| !event.kind.is_access())
+                        .flat_map(|event| event.event.paths)
+                        .filter(|path| path.extension().is_some_and(|ext| ext == "vpk"))
+                        .collect();
+                    if !paths.is_empty() {
+                        tx.send(paths).ok();
+                    }
+                }
+                Err(errors) => errors.iter().for_each(|e| warn!("watcher: {}", e)),
+            },
+        )
+        .map_err(|e| format!("failed to create watcher: {}", e))?;
+
+        debouncer
+            .watch(&addons_folder, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("failed to watch addons folder: {}", e))?;
+        let workshop_folder = addons_folder.join("workshop");
+        if workshop_folder.is_dir() {
+            debouncer
+                .watch(&workshop_folder, RecursiveMode::NonRecursive)
+                .map_err(|e| format!("failed to watch workshop folder: {}", e))?;
+        }
+
+        tauri::async_runtime::spawn(watch_main(
+            addons_folder.clone(),
+            rx,
+            self.addons.clone(),
+            self.app.clone(),
+        ));
+        self.debouncer = Some(debouncer);
+        info!("Watching {} for changes", addons_folder.display());
+        Ok(())
+    }
+
+    /// Stops watching, if running. The processing task ends once the pending events are handled
+    pub fn stop(&mut self) {
+        if let Some(debouncer) = self.debouncer.take() {
+            debug!("stopping watcher");
+            debouncer.stop_nonblocking();
+        }
+    }
+}
+
+/// Receives batches of changed paths from the debouncer and updates their entries
+async fn watch_main(
+    addons_folder: PathBuf,
+    mut rx: UnboundedReceiver<Vec<PathBuf>>,
+    addons: AddonStorageContainer,
+    app: AppHandle,
+) {
+    let workshop_folder = addons_folder.join("workshop");
+    while let Some(paths) = rx.recv().await {
+        // A file can appear in multiple events (create then modify), only process it once
+        let paths: HashSet<PathBuf> = paths.into_iter().collect();
+        trace!("watcher: got {} changed paths", paths.len());
+        // A running scan would mark entries stored with any other id as missing when it ends
+        let scan_id = match addons.read().await.current_scan_id().await {
+            Ok(id) => id,
+            Err(e) => {
+                error!("watcher: failed to get current scan: {}", e);
+                continue;
+            }
+        };
+        let mut changes: Vec<WatchedEntry> = Vec::new();
+        let mut removed_filenames: Vec<String> = Vec::new();
+        let mut workshop_ids: Vec<i64> = Vec::new();
+        let mut workshop_changed = false;
+        // Failures are recorded so they show as broken files, and earlier failures of stored files are cleared
+        let mut stored: Vec<String> = Vec::new();
+        let mut errors: Vec<ScanError> = Vec::new();
+        let record_id: u32 = random();
+        let mut record_error = |filename: &str, workshop_id: Option<i64>, error: String| {
+            errors.push(ScanError { scan_id: record_id as i64, filename: filename.to_string(), workshop_id, error });
+        };
+
+        for path in paths {
+            let Some(filename) = path.file_name().map(|f| f.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let exists = path.is_file();
+            let in_workshop = path.parent() == Some(workshop_folder.as_path());
+            if in_workshop {
+                // Workshop items are resolved by their ID, handled below
+                workshop_changed = true;
+            }
+            if !exists {
+                stored.push(filename.clone());
+                changes.push(WatchedEntry { filename: filename.clone(), workshop: in_workshop, change: WatchChange::Removed });
+                if !in_workshop {
+                    removed_filenames.push(filename);
+                }
+                continue;
+            }
+            let workshop_id = match in_workshop {
+                true => match path.file_stem().and_then(|stem| stem.to_string_lossy().parse::<i64>().ok()) {
+                    Some(id) => Some(id),
+                    None => continue,
+                },
+                false => None,
+            };
+
+            let thumbnails = app.state::<ThumbnailCache>().inner().clone();
+            let scan = move || scan_file(path, Some(&thumbnails));
+            let file = match tokio::task::spawn_blocking(scan).await {
+                Ok(Ok(file)) => file,
+                Ok(Err(e)) => {
+                    // Could still be mid copy, a later event will retry it and clear the error
+                    warn!("watcher: scan_file {}: {}", filename, e);
+                    record_error(&filename, workshop_id, e);
+                    continue;
+                }
+                Err(e) => {
+                    error!("watcher: scan task for {} failed: {}", filename, e);
+                    record_error(&filename, workshop_id, e.to_string());
+                    continue;
+                }
+            };
+            if let Some(workshop_id) = workshop_id {
+                let change = match async_process_workshop_file(file, workshop_id, addons.clone()).await {
+                    Ok(ProcessResult::Added) => WatchChange::Added,
+                    Ok(_) => WatchChange::Updated,
+                    Err(err) => {
+                        error!("watcher: process_workshop_file {}: {}", filename, err);
+                        record_error(&filename, Some(workshop_id), err.to_string());
+                        continue;
+                    }
+                };
+                stored.push(filename.clone());
+                changes.push(WatchedEntry { filename, workshop: true, change });
+                continue;
+            }
+            let change = match async_process_file(file, addons.clone(), scan_id).await {
+                Ok((ProcessResult::Added, workshop_id)) => {
+                    workshop_ids.extend(workshop_id);
+                    WatchChange::Added
+                }
+                Ok((ProcessResult::UpdatedByHash, _) | (ProcessResult::UpdatedByFilename, _)) => {
+                    WatchChange::Updated
+                }
+                Err(err) => {
+                    error!("watcher: process_file {}: {}", filename, err);
+                    record_error(&filename, None, err.to_string());
+                    continue;
+                }
+            };
+            stored.push(filename.clone());
+            changes.push(WatchedEntry { filename, workshop: false, change });
+        }
+
+        if !stored.is_empty() || !errors.is_empty() {
+            if let Err(e) = addons.read().await.record_outside_scan(record_id, "watcher", &stored, &errors).await {
+                error!("watcher: failed to record errors: {}", e);
+            }
+        }
+
+        if !removed_filenames.is_empty() {
+            let addons = addons.read().await;
+            if let Err(e) = addons.mark_missing_filenames(&removed_filenames).await {
+                error!("watcher: failed to mark missing files: {}", e);
+            }
+        }
+        if workshop_changed || !workshop_ids.is_empty() {
+            if let Err(e) = sync_workshop_items(&addons_folder, workshop_ids, &addons).await {
+                error!("watcher: failed to sync workshop items: {}", e);
+            }
+        }
+
+        if !changes.is_empty() {
+            debug!("watcher: {} entries changed", changes.len());
+            app.emit("addons_changed", changes).ok();
+        }
+    }
+    debug!("watcher: channel closed, ending");
+}
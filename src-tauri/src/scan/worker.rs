@@ -1,17 +1,30 @@
-use crate::modules::store::AddonStorageContainer;
-use crate::modules::store::{AddonData, AddonFlags, FileHash};
+use crate::models::scan::ScanError;
+use crate::modules::store::{AddonStorage, AddonStorageContainer};
+use crate::modules::store::{AddonData, AddonFlags, FileHash, FileStamp};
+use crate::modules::thumbnails::{ThumbnailCache, find_addon_image};
+use crate::modules::vpk::{VpkDirectory, VpkFile};
 use crate::scan::helpers::find_workshop_id;
-use crate::util::get_file_size;
+use crate::scan::ScanSignal;
+use crate::scan::throttle::IoThrottle;
 use l4d2_addon_parser::AddonInfo;
 use l4d2_addon_parser::L4D2Addon;
 use log::debug;
 use log::{error, trace, warn};
+use rand::random;
+use sha2::{Digest, Sha256};
 use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Read;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use sqlx::SqliteConnection;
+use sqlx::__rt::spawn_blocking;
 use steam_workshop_api::{SteamWorkshop, WorkshopItem};
 
+/// Bytes read at a time when hashing with a throttle
+const HASH_CHUNK_SIZE: usize = 1024 * 1024;
+
 #[derive(Debug)]
 pub enum ProcessResult {
     /// Existing addon found by hash, its info has been updated
@@ -26,6 +39,16 @@ pub enum ProcessError {
     FileError(std::io::Error),
     UpdateExistingError(sqlx::Error),
     NewEntryError(sqlx::Error),
+    IndexFilesError(sqlx::Error),
+    TransactionError(sqlx::Error),
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+/// Time scan_file took for a file
+pub struct FileTimings {
+    pub hash: Duration,
+    /// Reading the addon info, missions, file list and image
+    pub parse: Duration,
 }
 
 pub struct AddonFileData {
@@ -34,10 +57,60 @@ pub struct AddonFileData {
     info: AddonInfo,
     chapter_ids: Option<Vec<String>>,
     hash: FileHash,
+    /// Files inside the vpk, None if tree could not be read
+    files: Option<Vec<VpkFile>>,
+    /// Set if file is from the workshop folder
+    workshop_id: Option<i64>,
+    timings: FileTimings,
+}
+
+impl AddonFileData {
+    /// Workshop id of file, if it is from the workshop folder
+    pub fn workshop_id(&self) -> Option<i64> {
+        self.workshop_id
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn timings(&self) -> FileTimings {
+        self.timings
+    }
+}
+
+/// A file a worker could not scan, such as a corrupt vpk
+pub struct ScanFileError {
+    pub path: PathBuf,
+    pub filename: String,
+    pub workshop_id: Option<i64>,
+    pub error: String,
 }
 pub enum WorkerTask {
     /// Worker should scan a file
     ScanFile(PathBuf),
+    /// Worker should scan a file in the workshop folder, with its workshop id
+    ScanWorkshopFile(i64, PathBuf),
+}
+impl WorkerTask {
+    /// Returns task as (path, workshop id), for storing it in the scan queue
+    pub fn to_queue_entry(&self) -> (PathBuf, Option<i64>) {
+        match self {
+            WorkerTask::ScanFile(path) => (path.clone(), None),
+            WorkerTask::ScanWorkshopFile(id, path) => (path.clone(), Some(*id)),
+        }
+    }
+
+    pub fn from_queue_entry((path, workshop_id): (PathBuf, Option<i64>)) -> Self {
+        match workshop_id {
+            Some(id) => WorkerTask::ScanWorkshopFile(id, path),
+            None => WorkerTask::ScanFile(path),
+        }
+    }
 }
 // pub(super) enum WorkerOutput {
 //     /// Worker has new workshop id to enqueue
@@ -47,45 +120,55 @@ pub enum WorkerTask {
 // }
 
 /// Takes file paths from queue and scans them, extracting addon info, and a file hash
-/// Sends to main task on completion of scan
-/// Ends when queue is empty
+/// Sends to main task on completion of scan, blocking while the main task is behind on storing results
+/// Ends when queue is empty, and waits before taking a task while scan is paused
 pub fn scan_worker_thread(
     i: u8,
-    tx: tokio::sync::mpsc::UnboundedSender<Result<AddonFileData, String>>,
+    tx: tokio::sync::mpsc::Sender<Result<AddonFileData, ScanFileError>>,
     queue: Arc<tokio::sync::Mutex<VecDeque<WorkerTask>>>,
+    signal: Arc<ScanSignal>,
+    thumbnails: ThumbnailCache,
+    throttle: Option<Arc<IoThrottle>>,
 ) -> std::io::Result<()> {
     loop {
+        signal.wait_if_paused();
         trace!("[worker{i}] waiting for task");
         let task = {
             let mut queue = queue.blocking_lock();
             queue.pop_front()
         };
         trace!("[worker{i}] got task (is empty = {})", task.is_none());
-        match task {
-            Some(WorkerTask::ScanFile(path)) => {
-                let time = Instant::now();
-                match scan_file(path) {
-                    Ok(res) => {
-                        trace!(
-                            "[worker{i}] scan_file \"{}\" hash \"{}\" took {}ms",
-                            &res.filename,
-                            &res.hash,
-                            time.elapsed().as_millis()
-                        );
-                        if let Err(_) = tx.send(Ok(res)) {
-                            // we should never hopefully get this.
-                            // main task should always just empty queue and let all workers finish up
-                            trace!("[worker{i}] send error, main task gone, exiting...");
-                            break;
-                        }
-                        trace!("[worker{i}] sent result");
-                    }
-                    Err(e) => {
-                        warn!("[worker{i}] scan_file: {}", e);
-                    }
+        let (path, workshop_id) = match task {
+            Some(WorkerTask::ScanFile(path)) => (path, None),
+            Some(WorkerTask::ScanWorkshopFile(id, path)) => (path, Some(id)),
+            None => break,
+        };
+        let time = Instant::now();
+        let filename = path.file_name().unwrap().to_string_lossy().to_string();
+        match scan_file_throttled(path.clone(), throttle.as_deref(), Some(&thumbnails)) {
+            Ok(mut res) => {
+                res.workshop_id = workshop_id;
+                trace!(
+                    "[worker{i}] scan_file \"{}\" hash \"{}\" took {}ms",
+                    &res.filename,
+                    &res.hash,
+                    time.elapsed().as_millis()
+                );
+                if let Err(_) = tx.blocking_send(Ok(res)) {
+                    // we should never hopefully get this.
+                    // main task should always just empty queue and let all workers finish up
+                    trace!("[worker{i}] send error, main task gone, exiting...");
+                    break;
+                }
+                trace!("[worker{i}] sent result");
+            }
+            Err(error) => {
+                warn!("[worker{i}] scan_file {}: {}", filename, error);
+                if tx.blocking_send(Err(ScanFileError { path, filename, workshop_id, error })).is_err() {
+                    trace!("[worker{i}] send error, main task gone, exiting...");
+                    break;
                 }
             }
-            None => break,
         }
     }
     trace!("[worker{i}] done. exiting");
@@ -117,10 +200,20 @@ pub fn scan_workshop_thread(mut ids: Vec<i64>) -> Vec<WorkshopItem> {
     }
     results
 }
-/// returns info, missions, and hash (bytes)
-pub fn scan_file(path: PathBuf) -> Result<AddonFileData, String> {
+/// returns info, missions, and hash (bytes). Creates the addon's thumbnail if thumbnails is given and it has none yet
+pub fn scan_file(path: PathBuf, thumbnails: Option<&ThumbnailCache>) -> Result<AddonFileData, String> {
+    scan_file_throttled(path, None, thumbnails)
+}
+
+/// Same as scan_file, hashing the file no faster than throttle allows if given
+pub fn scan_file_throttled(
+    path: PathBuf,
+    throttle: Option<&IoThrottle>,
+    thumbnails: Option<&ThumbnailCache>,
+) -> Result<AddonFileData, String> {
     let filename = path.file_name().unwrap().to_string_lossy().to_string();
     trace!("scan_file:L4D2Addon {}", filename);
+    let time = Instant::now();
     let mut addon = L4D2Addon::from_path(&path).map_err(|e| format!("load addon: {}", e))?;
     trace!("scan_file:info {}", filename);
     let info = addon
@@ -139,8 +232,31 @@ pub fn scan_file(path: PathBuf) -> Result<AddonFileData, String> {
         }
     }
 
+    let mut parse_time = time.elapsed();
+
     trace!("scan_file:hash {}", filename);
-    let hash = addon.hash_256().map_err(|e| format!("hash addon: {}", e))?;
+    let time = Instant::now();
+    let hash = match throttle {
+        Some(throttle) => hash_file(&path, throttle).map_err(|e| format!("hash addon: {}", e))?,
+        None => addon.hash_256().map_err(|e| format!("hash addon: {}", e))?,
+    };
+    let hash_time = time.elapsed();
+    let hash = FileHash(hash);
+
+    trace!("scan_file:files {}", filename);
+    let time = Instant::now();
+    let dir = VpkDirectory::open(&path)
+        .inspect_err(|e| warn!("scan_file: could not read file list of {}: {}", filename, e))
+        .ok();
+    // Image is only read when the addon has no thumbnail yet
+    if let Some(thumbnails) = thumbnails.filter(|thumbnails| !thumbnails.has(&hash)) {
+        trace!("scan_file:image {}", filename);
+        if let Some(image) = find_addon_image(&path, dir.as_ref()) {
+            thumbnails.create(&hash, &image);
+        }
+    }
+    let files = dir.map(|dir| dir.files);
+    parse_time += time.elapsed();
 
     trace!("scan_file:done {}", filename);
     Ok(AddonFileData {
@@ -148,26 +264,88 @@ pub fn scan_file(path: PathBuf) -> Result<AddonFileData, String> {
         filename: filename.to_string(),
         info,
         chapter_ids,
-        hash: FileHash(hash),
+        hash,
+        files,
+        workshop_id: None,
+        timings: FileTimings {
+            hash: hash_time,
+            parse: parse_time,
+        },
     })
 }
 
+/// Sha256 of the whole file, same as L4D2Addon::hash_256, reading it in chunks the throttle allows
+fn hash_file(path: &PathBuf, throttle: &IoThrottle) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; HASH_CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        throttle.wait(read as u64);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
 /// Tries to find existing addon entry by file hash, and update any meta info
 /// Otherwise, adds new entry to db
 pub async fn async_process_file(
     file: AddonFileData,
     addons: AddonStorageContainer,
     scan_id: u32,
+) -> Result<(ProcessResult, Option<i64>), ProcessError> {
+    let addons = addons.read().await;
+    let mut tx = addons.begin().await.map_err(|e| ProcessError::TransactionError(e))?;
+    let result = process_file(&mut tx, file, scan_id).await?;
+    tx.commit().await.map_err(|e| ProcessError::TransactionError(e))?;
+    Ok(result)
+}
+
+/// Scans and stores a file written outside of a scan, such as a copied or updated addon, by source ('copy' or 'update').
+/// A failure is recorded so the file shows as broken, and a success clears earlier failures of it
+pub async fn async_store_outside_scan(
+    path: PathBuf,
+    addons: &AddonStorageContainer,
+    thumbnails: &ThumbnailCache,
+    source: &str,
+) -> Result<(), String> {
+    let filename = path.file_name().unwrap().to_string_lossy().to_string();
+    let thumbnails = thumbnails.clone();
+    let result = match spawn_blocking(move || scan_file(path, Some(&thumbnails))).await {
+        Ok(file) => match addons.read().await.current_scan_id().await {
+            Ok(scan_id) => async_process_file(file, addons.clone(), scan_id).await.map(|_| ()).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        },
+        Err(e) => Err(e),
+    };
+    let record_id: u32 = random();
+    let (stored, errors) = match &result {
+        Ok(()) => (vec![filename], vec![]),
+        Err(error) => (vec![], vec![ScanError { scan_id: record_id as i64, filename, workshop_id: None, error: error.clone() }]),
+    };
+    if let Err(e) = addons.read().await.record_outside_scan(record_id, source, &stored, &errors).await {
+        error!("{}: failed to record errors: {}", source, e);
+    }
+    result
+}
+
+/// Same as async_process_file, but on a connection so it can be part of a larger transaction
+pub async fn process_file(
+    conn: &mut SqliteConnection,
+    file: AddonFileData,
+    scan_id: u32,
 ) -> Result<(ProcessResult, Option<i64>), ProcessError> {
     let meta = file
         .path
         .metadata()
         .map_err(|e| ProcessError::FileError(e))?;
+    let stamp = FileStamp::from_meta(&meta);
     trace!("process_file \"{}\"", &file.filename);
 
-    let mut addons = addons.lock().await;
-    if addons
-        .update_entry_by_hash(&file.hash, &file.filename, &file.info, Some(scan_id))
+    if AddonStorage::update_entry_by_hash(conn, &file.hash, &file.filename, &file.info, &stamp, Some(scan_id))
         .await
         .map_err(|e| ProcessError::UpdateExistingError(e))?
     {
@@ -175,9 +353,9 @@ pub async fn async_process_file(
             "found existing file: \"{}\" by hash \"{}\"",
             file.filename, file.hash
         );
+        index_files(conn, &file.hash, file.files).await?;
         return Ok((ProcessResult::UpdatedByHash, None));
-    } else if addons
-        .update_entry_by_filename(&file.hash, &file.filename, &file.info, Some(scan_id))
+    } else if AddonStorage::update_entry_by_filename(conn, &file.hash, &file.filename, &file.info, &stamp, Some(scan_id))
         .await
         .map_err(|e| ProcessError::UpdateExistingError(e))?
     {
@@ -185,6 +363,7 @@ pub async fn async_process_file(
             "found existing file: \"{}\" by filename",
             file.filename
         );
+        index_files(conn, &file.hash, file.files).await?;
         return Ok((ProcessResult::UpdatedByFilename, None));
     }
 
@@ -193,7 +372,7 @@ pub async fn async_process_file(
     // Treat file as new now
     let flags: AddonFlags = (&file.info.content).into();
     let data = AddonData {
-        filename: file.filename.to_string(),
+        filename: Some(file.filename.to_string()),
         updated_at: meta
             .modified()
             .map_err(|e| ProcessError::FileError(e))?
@@ -202,7 +381,7 @@ pub async fn async_process_file(
             .created()
             .map_err(|e| ProcessError::FileError(e))?
             .into(),
-        file_size: get_file_size(&meta),
+        file_size: stamp.file_size,
         flags: flags,
         title: file.info.title.unwrap_or_else(|| file.filename.to_string()), // TODO: if no info/info.title, use filename?
         author: file.info.author,
@@ -213,12 +392,79 @@ pub async fn async_process_file(
     };
 
     // Add to DB
-    addons
-        .add_entry(&data, Some(scan_id), file.hash)
+    AddonStorage::add_entry(conn, &data, stamp.file_mtime, Some(scan_id), file.hash.clone())
         .await
         .map_err(|e| ProcessError::NewEntryError(e))?;
+    index_files(conn, &file.hash, file.files).await?;
 
     debug!("found new addon: \"{}\"", file.filename);
 
     Ok((ProcessResult::Added, data.workshop_id))
 }
+
+/// Stores the file list of addon's vpk. A vpk whose tree could not be read is still marked indexed,
+/// so it is not rescanned every scan
+async fn index_files(
+    conn: &mut SqliteConnection,
+    hash: &FileHash,
+    files: Option<Vec<VpkFile>>,
+) -> Result<(), ProcessError> {
+    AddonStorage::set_addon_files(conn, hash, files.as_deref().unwrap_or_default())
+        .await
+        .map_err(|e| ProcessError::IndexFilesError(e))
+}
+
+/// Stores the parsed info of a workshop folder vpk, linked to its workshop item by id
+pub async fn async_process_workshop_file(
+    file: AddonFileData,
+    workshop_id: i64,
+    addons: AddonStorageContainer,
+) -> Result<ProcessResult, ProcessError> {
+    let addons = addons.read().await;
+    let mut tx = addons.begin().await.map_err(|e| ProcessError::TransactionError(e))?;
+    let result = process_workshop_file(&mut tx, file, workshop_id).await?;
+    tx.commit().await.map_err(|e| ProcessError::TransactionError(e))?;
+    Ok(result)
+}
+
+/// Same as async_process_workshop_file, but on a connection so it can be part of a larger transaction
+pub async fn process_workshop_file(
+    conn: &mut SqliteConnection,
+    file: AddonFileData,
+    workshop_id: i64,
+) -> Result<ProcessResult, ProcessError> {
+    let meta = file
+        .path
+        .metadata()
+        .map_err(|e| ProcessError::FileError(e))?;
+    let stamp = FileStamp::from_meta(&meta);
+    trace!("process_workshop_file \"{}\"", &file.filename);
+
+    let existed = AddonStorage::set_workshop_file(conn, workshop_id, &file.hash, &file.info, file.chapter_ids.map(|c| c.join(",")), &stamp)
+        .await
+        .map_err(|e| ProcessError::NewEntryError(e))?;
+    if existed {
+        debug!("updated workshop file: \"{}\"", file.filename);
+        Ok(ProcessResult::UpdatedByFilename)
+    } else {
+        debug!("found new workshop file: \"{}\"", file.filename);
+        Ok(ProcessResult::Added)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::{build_vpk, temp_dir};
+
+    #[test]
+    fn throttled_hash_matches_parser_hash() {
+        let path = temp_dir("hash").join("test.vpk");
+        // Spans several chunks, with a partial one at the end
+        let data: Vec<u8> = (0..HASH_CHUNK_SIZE * 2 + 123).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, build_vpk(&[("addoninfo.txt", b"\"AddonInfo\" {}"), ("data.bin", &data)])).unwrap();
+
+        let throttled = hash_file(&path, &IoThrottle::new(u64::MAX)).unwrap();
+        assert_eq!(throttled, L4D2Addon::from_path(&path).unwrap().hash_256().unwrap());
+    }
+}
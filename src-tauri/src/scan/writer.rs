@@ -0,0 +1,127 @@
+use crate::models::scan::ScanTimings;
+use crate::modules::store::{AddonStorage, AddonStorageContainer};
+use crate::scan::worker::{AddonFileData, ProcessResult, ScanFileError, process_file, process_workshop_file};
+use log::{error, trace};
+use sqlx::Connection;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Receiver;
+
+/// Most results stored in one transaction
+pub(super) const WRITE_BATCH_SIZE: usize = 100;
+/// How long to wait for a batch to fill up before storing what there is
+const WRITE_BATCH_WAIT: Duration = Duration::from_millis(250);
+
+/// A file of a stored batch, and what happened to it
+pub(super) struct WrittenFile {
+    pub path: PathBuf,
+    pub filename: String,
+    /// Set if file is in the workshop folder
+    pub workshop_id: Option<i64>,
+    /// Result and workshop id of a new addon, or why the file could not be scanned or stored
+    pub result: Result<(ProcessResult, Option<i64>), String>,
+}
+
+/// Waits for results of worker threads, returning up to WRITE_BATCH_SIZE of them.
+/// Returns false once all workers are done
+pub(super) async fn recv_batch(
+    rx: &mut Receiver<Result<AddonFileData, ScanFileError>>,
+    batch: &mut Vec<Result<AddonFileData, ScanFileError>>,
+) -> bool {
+    if rx.recv_many(batch, WRITE_BATCH_SIZE).await == 0 {
+        return false;
+    }
+    let started = Instant::now();
+    while batch.len() < WRITE_BATCH_SIZE {
+        let Some(left) = WRITE_BATCH_WAIT.checked_sub(started.elapsed()) else { break };
+        match sqlx::__rt::timeout(left, rx.recv_many(batch, WRITE_BATCH_SIZE - batch.len())).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+    true
+}
+
+/// Stores a batch of worker results in one transaction, and removes them from the scan's stored queue in it.
+/// Each file gets its own savepoint, so a file that fails to store does not undo the rest of the batch
+pub(super) async fn write_batch(
+    addons: &AddonStorageContainer,
+    scan_id: u32,
+    batch: Vec<Result<AddonFileData, ScanFileError>>,
+    timings: &mut ScanTimings,
+) -> Vec<WrittenFile> {
+    let mut files: Vec<(WrittenFile, Option<AddonFileData>)> = batch
+        .into_iter()
+        .map(|result| match result {
+            Ok(file) => {
+                let file_timings = file.timings();
+                timings.hash += file_timings.hash;
+                timings.parse += file_timings.parse;
+                let written = WrittenFile {
+                    path: file.path().clone(),
+                    filename: file.filename().to_string(),
+                    workshop_id: file.workshop_id(),
+                    result: Err("not stored".to_string()),
+                };
+                (written, Some(file))
+            }
+            Err(err) => {
+                let written = WrittenFile {
+                    path: err.path,
+                    filename: err.filename,
+                    workshop_id: err.workshop_id,
+                    result: Err(err.error),
+                };
+                (written, None)
+            }
+        })
+        .collect();
+    timings.files += files.len() as u32;
+
+    let addons = addons.read().await;
+    let time = Instant::now();
+    if let Err(e) = write_files(&addons, scan_id, &mut files).await {
+        error!("failed to store batch of {} files: {}", files.len(), e);
+        // Whole transaction was rolled back
+        for (file, data) in files.iter_mut() {
+            if file.result.is_ok() || data.is_some() {
+                file.result = Err(format!("failed to store: {}", e));
+            }
+        }
+    }
+    timings.db += time.elapsed();
+    trace!("stored batch of {} files in {}ms", files.len(), time.elapsed().as_millis());
+    files.into_iter().map(|(file, _)| file).collect()
+}
+
+async fn write_files(
+    addons: &AddonStorage,
+    scan_id: u32,
+    files: &mut [(WrittenFile, Option<AddonFileData>)],
+) -> Result<(), sqlx::Error> {
+    let mut tx = addons.begin().await?;
+    for (file, data) in files.iter_mut() {
+        let Some(data) = data.take() else { continue };
+        let mut savepoint = tx.begin().await?;
+        let result = match file.workshop_id {
+            Some(workshop_id) => process_workshop_file(&mut savepoint, data, workshop_id)
+                .await
+                .map(|result| (result, None)),
+            None => process_file(&mut savepoint, data, scan_id).await,
+        };
+        match result {
+            Ok(result) => {
+                savepoint.commit().await?;
+                file.result = Ok(result);
+            }
+            Err(e) => {
+                savepoint.rollback().await?;
+                error!("process_file \"{}\": {}", file.filename, e);
+                file.result = Err(e.to_string());
+            }
+        }
+    }
+    let paths: Vec<PathBuf> = files.iter().map(|(file, _)| file.path.clone()).collect();
+    AddonStorage::remove_scan_queue(&mut tx, scan_id, &paths).await?;
+    tx.commit().await
+}
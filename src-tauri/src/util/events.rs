@@ -0,0 +1,29 @@
+use log::warn;
+use serde::Serialize;
+use std::sync::Arc;
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, Emitter};
+
+/// Receives the events of long running tasks, such as scan progress.
+/// The app sends them to the webview, the cli prints them
+pub trait EventSink: Send + Sync {
+    fn emit_json(&self, event: &str, payload: serde_json::Value);
+}
+
+impl dyn EventSink {
+    pub fn emit<S: Serialize>(&self, event: &str, payload: S) {
+        match serde_json::to_value(payload) {
+            Ok(payload) => self.emit_json(event, payload),
+            Err(e) => warn!("failed to serialize {} event: {}", event, e),
+        }
+    }
+}
+
+pub type EventSinkContainer = Arc<dyn EventSink>;
+
+#[cfg(feature = "gui")]
+impl EventSink for AppHandle {
+    fn emit_json(&self, event: &str, payload: serde_json::Value) {
+        Emitter::emit(self, event, payload).ok();
+    }
+}
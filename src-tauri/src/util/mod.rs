@@ -1,11 +1,20 @@
 pub mod defs;
+pub mod events;
+pub mod runtime;
+#[cfg(test)]
+pub(crate) mod test_util;
 
+#[cfg(feature = "gui")]
 use crate::modules::cfg::AppConfigContainer;
 use l4d2_addon_parser::addon_list::AddonList;
 use log::warn;
 use serde::Serialize;
+use serde_with::SerializeDisplay;
 use std::fmt::{Display, Formatter};
 use std::fs::Metadata;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+#[cfg(feature = "gui")]
 use tauri::{AppHandle, Emitter, State};
 
 #[derive(Debug, Serialize)]
@@ -13,6 +22,7 @@ pub struct SetRoute {
     pub name: Option<String>,
 }
 
+#[cfg(feature = "gui")]
 pub async fn get_addon_list(cfg: State<'_, AppConfigContainer>) -> Option<AddonList> {
     let cfg = cfg.lock().await;
     cfg.addons_folder.as_ref().and_then(|folder| {
@@ -26,7 +36,23 @@ pub async fn get_addon_list(cfg: State<'_, AppConfigContainer>) -> Option<AddonL
     })
 }
 
-#[derive(Debug, Serialize, Clone)]
+/// Edits addonlist.txt on a temporary copy, then replaces the original in one step,
+/// so the game never sees a partially applied list
+pub fn edit_addon_list<T>(path: &PathBuf, edit: impl FnOnce(&mut AddonList) -> T) -> Result<T, String> {
+    let tmp_path = path.with_extension("txt.tmp");
+    if path.exists() {
+        std::fs::copy(path, &tmp_path).map_err(|e| format!("failed to copy addonlist.txt: {}", e))?;
+    }
+    let mut list = AddonList::new(&tmp_path).map_err(|e| format!("failed to read addonlist.txt: {}", e))?;
+    let result = edit(&mut list);
+    list.save()
+        .map_err(|e| format!("failed to save addonlist.txt: {}", e))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| format!("failed to replace addonlist.txt: {}", e))?;
+    Ok(result)
+}
+
+#[derive(Debug, SerializeDisplay, Clone)]
+/// Sent as its name, which the frontend uses as the notification's style
 pub enum NotificationType {
     Info,
     Error,
@@ -59,6 +85,7 @@ impl Notification {
         }
     }
 
+    #[cfg(feature = "gui")]
     pub fn send(self, app: &AppHandle) {
         app.emit("notify", self)
             .expect("failed to send notification");
@@ -76,3 +103,11 @@ pub fn get_file_size(meta: &Metadata) -> i64 {
     #[cfg(windows)]
     return meta.file_size() as i64;
 }
+
+/// Returns the file's modification time in milliseconds since unix epoch, if available
+pub fn get_file_mtime(meta: &Metadata) -> Option<i64> {
+    meta.modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|dur| dur.as_millis() as i64)
+}
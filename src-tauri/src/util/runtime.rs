@@ -0,0 +1,17 @@
+use std::future::Future;
+
+/// Runs future to completion on tauri's runtime, which the app's tasks also use
+#[cfg(feature = "gui")]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tauri::async_runtime::block_on(future)
+}
+
+/// Runs future to completion on the runtime of the cli, which is built without tauri
+#[cfg(not(feature = "gui"))]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    use std::sync::OnceLock;
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME
+        .get_or_init(|| tokio::runtime::Runtime::new().expect("failed to start async runtime"))
+        .block_on(future)
+}
@@ -0,0 +1,100 @@
+use rand::random;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+
+/// Creates an empty folder under the system temp folder
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("l4d2-addon-manager-{}-{}", name, random::<u32>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Builds a version 1 vpk with all file data in the directory file, the way addon vpks are stored
+pub fn build_vpk(files: &[(&str, &[u8])]) -> Vec<u8> {
+    // Tree is grouped by extension, then directory. " " is an empty part
+    let mut tree: BTreeMap<String, BTreeMap<String, Vec<(String, &[u8])>>> = BTreeMap::new();
+    for (path, data) in files {
+        let (dir, file) = path.rsplit_once('/').unwrap_or((" ", path));
+        let (name, ext) = file.rsplit_once('.').unwrap_or((file, " "));
+        tree.entry(ext.to_string())
+            .or_default()
+            .entry(dir.to_string())
+            .or_default()
+            .push((name.to_string(), data));
+    }
+    let mut tree_data = Vec::new();
+    let mut file_data = Vec::new();
+    for (ext, dirs) in &tree {
+        tree_data.extend(ext.as_bytes());
+        tree_data.push(0);
+        for (dir, names) in dirs {
+            tree_data.extend(dir.as_bytes());
+            tree_data.push(0);
+            for (name, data) in names {
+                tree_data.extend(name.as_bytes());
+                tree_data.push(0);
+                tree_data.extend(crc32(data).to_le_bytes());
+                tree_data.extend(0u16.to_le_bytes()); // preload size
+                tree_data.extend(0x7FFFu16.to_le_bytes()); // stored in directory file
+                tree_data.extend((file_data.len() as u32).to_le_bytes());
+                tree_data.extend((data.len() as u32).to_le_bytes());
+                tree_data.extend(0xFFFFu16.to_le_bytes());
+                file_data.extend(*data);
+            }
+            tree_data.push(0);
+        }
+        tree_data.push(0);
+    }
+    tree_data.push(0);
+
+    let mut vpk = Vec::new();
+    vpk.extend(0x55AA1234u32.to_le_bytes());
+    vpk.extend(1u32.to_le_bytes());
+    vpk.extend((tree_data.len() as u32).to_le_bytes());
+    vpk.extend(tree_data);
+    vpk.extend(file_data);
+    vpk
+}
+
+/// Builds an addon vpk with just an addoninfo.txt
+pub fn addon_vpk(title: &str, version: &str) -> Vec<u8> {
+    let info = format!(
+        "\"AddonInfo\"\n{{\n\taddonTitle \"{}\"\n\taddonVersion \"{}\"\n\taddonAuthor \"test\"\n\taddonDescription \"test addon\"\n}}\n",
+        title, version
+    );
+    build_vpk(&[("addoninfo.txt", info.as_bytes())])
+}
+
+/// Serves body to the first request on a local port, returning its url
+pub fn serve_once(body: Vec<u8>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/file.vpk", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|end| end == b"\r\n\r\n") {
+            let read = stream.read(&mut buf).unwrap();
+            if read == 0 {
+                break;
+            }
+            request.extend(&buf[..read]);
+        }
+        write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len()).unwrap();
+        stream.write_all(&body).unwrap();
+    });
+    url
+}
@@ -1,9 +1,10 @@
-import { invoke, InvokeArgs, InvokeOptions } from '@tauri-apps/api/core'
-import { AddonEntry } from '../types/Addon.ts';
+import { convertFileSrc, invoke, InvokeArgs, InvokeOptions } from '@tauri-apps/api/core'
+import { AddonConflict, AddonFileRef, AddonFilter, AddonPage, DuplicateGroup, OutdatedAddon, SortKey, VpkTreeNode, WorkshopSubscription } from '../types/Addon.ts';
 import { notify } from '@kyvg/vue3-notification';
-import { AddonCounts, AppConfig, InitAppData, ItemResult, LogEntry } from '../types/App.ts';
+import { AddonCounts, AppConfig, FastDlSource, FastDlSummary, ImportSummary, InitAppData, Installation, InstallationList, ItemResult, LogEntry, ServerConfigOutput } from '../types/App.ts';
 import { handleItemResults } from './app.ts';
-import { ScanSpeed } from '../types/Scan.ts';
+import { ScanError, ScanRecord, ScanSpeed } from '../types/Scan.ts';
+import { CollectionImport, Profile, ProfileCopy, ProfileItems } from '../types/Profile.ts';
 import { SelectedSort } from '../components/SortableColumnHeader.vue';
 
 async function tryInvoke<T>(cmd: string, args?: InvokeArgs, options?: InvokeOptions): Promise<T> {
@@ -28,9 +29,10 @@ export async function countAddons(): Promise<AddonCounts> {
     }
 }
 
-export async function listAddons(workshop = false, sort?: SelectedSort): Promise<AddonEntry[]> {
+/** sort can be multiple keys, later keys used when earlier are equal */
+export async function listAddonsPage(workshop = false, sort?: SelectedSort | SortKey[], filter?: AddonFilter): Promise<AddonPage> {
     const key = workshop ? "addons_list_workshop" : "addons_list_managed"
-    return await tryInvoke(key, { sort })
+    return await tryInvoke(key, { sort, filter })
 }
 
 export async function getGameFolder(): Promise<string | null> {
@@ -45,12 +47,48 @@ export async function setConfig(config: AppConfig): Promise<void> {
     return await tryInvoke("set_config", { config })
 }
 
+/** Writes workshop collection and download cvars of server to left4dead2/cfg/addon_manager.cfg. Server mode only */
+export async function generateServerConfig(): Promise<ServerConfigOutput> {
+    return await tryInvoke("server_generate_config")
+}
+
+export async function listInstallations(): Promise<InstallationList> {
+    return await tryInvoke("installations_list")
+}
+
+export async function addInstallation(name: string, addonsFolder: string): Promise<Installation> {
+    return await tryInvoke("installations_add", { name, addonsFolder })
+}
+
+/** Deletes installation and its database */
+export async function removeInstallation(id: number): Promise<void> {
+    return await tryInvoke("installations_remove", { id })
+}
+
+/** Makes installation active, config_changed is emitted with its addons folder */
+export async function selectInstallation(id: number): Promise<void> {
+    return await tryInvoke("installations_select", { id })
+}
+
+/** Copies managed addons by id (hash) into another installation's addons folder */
+export async function copyAddonsToInstallation(from: number, to: number, ids: string[]): Promise<ItemResult[]> {
+    const results: ItemResult[] = await tryInvoke("installations_copy_addons", { from, to, ids })
+    handleItemResults(results)
+    return results
+}
+
+/** Copies a profile into another installation, its addons are not copied */
+export async function copyProfileToInstallation(from: number, to: number, profileId: number): Promise<ProfileCopy> {
+    return await tryInvoke("installations_copy_profile", { from, to, profileId })
+}
+
 export async function init(): Promise<InitAppData> {
     return await tryInvoke("init")
 }
 
 
-export async function startScan(speed: ScanSpeed = ScanSpeed.Normal): Promise<void> {
+/** Starts a scan, at the configured speed if not given one */
+export async function startScan(speed?: ScanSpeed): Promise<void> {
     return await tryInvoke("addons_start_scan", { speed })
 }
 
@@ -58,6 +96,38 @@ export async function abortScan(reason?: string): Promise<void> {
     return await tryInvoke("addons_abort_scan", { reason })
 }
 
+export async function pauseScan(): Promise<void> {
+    return await tryInvoke("addons_pause_scan")
+}
+
+export async function resumeScan(): Promise<void> {
+    return await tryInvoke("addons_resume_scan")
+}
+
+/** Returns scan that never ended, such as one interrupted by the app closing */
+export async function getUnfinishedScan(): Promise<ScanRecord | null> {
+    return await tryInvoke("addons_unfinished_scan")
+}
+
+/** Continues unfinished scan from its remaining files */
+export async function resumeUnfinishedScan(speed?: ScanSpeed): Promise<void> {
+    return await tryInvoke("addons_resume_unfinished_scan", { speed })
+}
+
+/** Returns past scans, newest first */
+export async function getScanHistory(limit?: number): Promise<ScanRecord[]> {
+    return await tryInvoke("addons_scan_history", { limit })
+}
+
+export async function getScanErrors(id: number): Promise<ScanError[]> {
+    return await tryInvoke("addons_scan_errors", { id })
+}
+
+/** Files the latest scan could not add, such as corrupt vpks */
+export async function getBrokenAddons(): Promise<ScanError[]> {
+    return await tryInvoke("addons_broken")
+}
+
 export async function migrateWorkshopAddons(ids: number[]): Promise<ItemResult[]> {
     const results: ItemResult[] = await tryInvoke("addons_migrate", { ids })
     const errors = handleItemResults(results)
@@ -134,10 +204,98 @@ export async function deleteAddons(filenames: string[]): Promise<ItemResult[]> {
     }
     return results
 }
+export async function findDuplicates(): Promise<DuplicateGroup[]> {
+    return await tryInvoke("addons_duplicates")
+}
+
+/** Keeps one file of a duplicate group, moving the others to trash */
+export async function resolveDuplicates(keep: AddonFileRef, remove: AddonFileRef[]): Promise<ItemResult[]> {
+    const results: ItemResult[] = await tryInvoke("addons_resolve_duplicates", { keep, remove })
+    const errors = handleItemResults(results)
+    if(errors === 0) {
+        notify({
+            type: "success",
+            title: "Duplicates removed",
+            text: `${results.length} duplicate files have been moved to trash`
+        })
+    } else {
+        notify({
+            type: "warn",
+            title: "Some duplicates could not be removed",
+            text: `${errors} / ${results.length} files could not be removed. See logs for info`
+        })
+    }
+    return results
+}
+
+/** Fetches workshop info of managed addons linked to a workshop item, or only the given ids. Returns number of items refreshed */
+export async function refreshWorkshopInfo(ids?: number[]): Promise<number> {
+    return await tryInvoke("addons_refresh_workshop", { ids })
+}
+
+/** Lists managed addons that have a newer version on the workshop */
+export async function checkAddonUpdates(): Promise<OutdatedAddon[]> {
+    return await tryInvoke("addons_check_updates")
+}
+
+/** Downloads the latest workshop version of managed addons (by entry id), replacing their files */
+export async function updateAddons(ids: string[]): Promise<ItemResult[]> {
+    const results: ItemResult[] = await tryInvoke("addons_update", { ids })
+    const errors = handleItemResults(results)
+    if(errors === 0) {
+        notify({
+            type: "success",
+            title: "Addons updated",
+            text: `${results.length} addons have been updated`
+        })
+    } else {
+        notify({
+            type: "warn",
+            title: "Some addons could not be updated",
+            text: `${errors} / ${results.length} addons could not be updated. See logs for info`
+        })
+    }
+    return results
+}
+
+/** Lists workshop subscriptions from Steam's records, including pending and partial downloads. Works offline */
+export async function getWorkshopSubscriptions(): Promise<WorkshopSubscription[]> {
+    return await tryInvoke("addons_workshop_subscriptions")
+}
+
+/** Lists files that more than one enabled addon replaces */
+export async function findConflicts(): Promise<AddonConflict[]> {
+    return await tryInvoke("addons_conflicts")
+}
+
+/** URL of addon's cached thumbnail by entry id (hash or workshop id), responds with 404 if addon has no image */
+export function getThumbnailUrl(id: string): string {
+    return convertFileSrc(id, "thumbnail")
+}
+
+/** Returns the directory tree of files inside addon */
+export async function getAddonFiles(id: string): Promise<VpkTreeNode[]> {
+    return await tryInvoke("addons_vpk_tree", { id })
+}
+
+/** Extracts a file or directory from addon into dest folder, empty path extracts everything. Returns number of files extracted */
+export async function extractAddonFiles(id: string, path: string, dest: string): Promise<number> {
+    return await tryInvoke("addons_vpk_extract", { id, path, dest })
+}
+
 export async function exportApp(withAddons: boolean): Promise<void> {
     return await tryInvoke("export", { withAddons })
 }
 
+/** Writes a fastdl tree (.bz2 copies of maps and custom assets) of addons, into dest or a folder the user picks. Progress is sent as fastdl_progress */
+export async function exportFastDl(source: FastDlSource, dest?: string): Promise<FastDlSummary> {
+    return await tryInvoke("export_fastdl", { source, dest })
+}
+
+export async function cancelFastDlExport(): Promise<void> {
+    return await tryInvoke("export_fastdl_cancel")
+}
+
 export async function resetDatabase(): Promise<void> {
     return await tryInvoke("reset_db")
 }
@@ -161,4 +319,53 @@ export async function addTag(entryId: string, tag: string) {
 
 export async function removeTag(entryId: string, tag: string) {
     return await tryInvoke("addons_tag_del", { id: entryId, tag })
-}
\ No newline at end of file
+}
+export async function listProfiles(): Promise<Profile[]> {
+    return await tryInvoke("profiles_list")
+}
+
+/** If no items provided, the currently enabled addons are used. Returns id of new profile */
+export async function createProfile(name: string, items?: ProfileItems): Promise<number> {
+    return await tryInvoke("profiles_create", { name, items })
+}
+
+/** Creates a profile from a workshop collection url or id, named after the collection if no name given */
+export async function importCollection(collection: string, name?: string): Promise<CollectionImport> {
+    return await tryInvoke("profiles_import_collection", { collection, name })
+}
+
+export async function updateProfile(id: number, name?: string, items?: ProfileItems): Promise<void> {
+    return await tryInvoke("profiles_update", { id, name, items })
+}
+
+export async function duplicateProfile(id: number, name: string): Promise<number> {
+    return await tryInvoke("profiles_duplicate", { id, name })
+}
+
+export async function deleteProfile(id: number): Promise<void> {
+    return await tryInvoke("profiles_delete", { id })
+}
+
+export async function applyProfile(id: number): Promise<ItemResult[]> {
+    const results: ItemResult[] = await tryInvoke("profiles_apply", { id })
+    const errors = handleItemResults(results)
+    if(errors === 0) {
+        notify({
+            type: "success",
+            title: "Profile applied",
+            text: `${results.length} addons have been enabled`
+        })
+    } else {
+        notify({
+            type: "warn",
+            title: "Profile applied with errors",
+            text: `${errors} / ${results.length} addons could not be enabled. See logs for info`
+        })
+    }
+    return results
+}
+
+/** With dryRun, returns what the export contains. Otherwise app restarts once import is done */
+export async function importApp(dryRun: boolean, options: { path?: string, configMode?: "merge" | "replace", withAddons?: boolean } = {}): Promise<ImportSummary> {
+    return await tryInvoke("import", { dryRun, path: options.path, configMode: options.configMode, withAddons: options.withAddons ?? false })
+}
@@ -1,5 +1,6 @@
 export interface AddonData {
-    filename: string,
+    /** null if file is missing */
+    filename: string | null,
     /** ISO Date, parse as Date */
     updated_at: string
     /** ISO Date, parse as Date */
@@ -22,6 +23,131 @@ export interface AddonEntry {
     enabled: boolean
 }
 
+export interface AddonFilter {
+    /** full text search */
+    query?: string,
+    /** AddonFlags bits that must all be set */
+    flags?: number,
+    enabled?: boolean,
+    missing?: boolean,
+    workshop_linked?: boolean,
+    min_size?: number,
+    max_size?: number,
+    offset?: number,
+    limit?: number
+}
+
+export interface SortKey {
+    field: string,
+    descending?: boolean,
+    nulls?: "default" | "first" | "last"
+}
+
+/** returned by list commands on failure */
+export type ListError = ListError_InvalidSort | ListError_TooManySortKeys | ListError_Database
+export interface ListError_InvalidSort {
+    kind: "invalid_sort",
+    message: string,
+    field: string,
+    allowed: string[]
+}
+export interface ListError_TooManySortKeys {
+    kind: "too_many_sort_keys",
+    message: string,
+    max: number
+}
+export interface ListError_Database {
+    kind: "database",
+    message: string
+}
+
+export interface AddonPage {
+    entries: AddonEntry[],
+    /** total entries matching filter, across all pages */
+    total: number
+}
+
+export interface AddonFileRef {
+    filename: string,
+    /** is file in the workshop folder */
+    workshop: boolean
+}
+
+export interface DuplicateFile extends AddonFileRef {
+    file_size: number,
+    /** null if addonlist.txt could not be read */
+    enabled: boolean | null
+}
+
+export interface DuplicateGroup {
+    reason: "same_hash" | "same_workshop_id",
+    workshop_id: number | null,
+    hash: string | null,
+    files: DuplicateFile[]
+}
+
+export interface ConflictAddon {
+    /** entry id (hash) */
+    id: string,
+    filename: string,
+    title: string
+}
+
+export interface AddonConflict {
+    /** addon whose files are used in game, under current load order */
+    winner: ConflictAddon,
+    loser: ConflictAddon,
+    total: number,
+    categories: {
+        category: "models" | "materials" | "sound" | "scripts" | "other",
+        paths: string[]
+    }[]
+}
+
+export interface VpkFile {
+    path: string,
+    size: number,
+    crc: number
+}
+
+export type VpkTreeNode = {
+    type: "dir",
+    name: string,
+    path: string,
+    /** total size of all files in directory */
+    size: number,
+    children: VpkTreeNode[]
+} | ({
+    type: "file",
+    name: string
+} & VpkFile)
+
+/** managed addon with a newer version on the workshop */
+export interface OutdatedAddon {
+    /** entry id (hash) */
+    id: string,
+    filename: string,
+    title: string,
+    workshop_id: number,
+    /** unix seconds */
+    file_updated: number,
+    /** unix seconds */
+    workshop_updated: number
+}
+
+/** workshop subscription, as recorded by Steam */
+export interface WorkshopSubscription {
+    workshop_id: number,
+    state: "installed" | "pending" | "partial" | "needs_update",
+    /** size of installed version, null if never installed */
+    size: number | null,
+    /** size of vpk in workshop folder, null if missing */
+    file_size: number | null,
+    /** unix seconds */
+    time_updated: number | null,
+    manifest: string | null
+}
+
 export interface WorkshopItem {
     publishedfileid: string,
     title: string,
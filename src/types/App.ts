@@ -1,4 +1,5 @@
 import { LogLevel } from "@tauri-apps/plugin-log"
+import { ScanSpeed } from "./Scan"
 
 export interface SetRoute {
     name?: string
@@ -27,7 +28,47 @@ export interface AppConfig {
     addons_folder: string | null,
     steam_apikey: string | null,
     startup_scan: boolean,
-    startup_telemetry: boolean
+    startup_telemetry: boolean,
+    /** speed of scans the user starts */
+    scan_speed: ScanSpeed,
+    /** hours between scheduled scans, null to not scan periodically */
+    scan_interval_hours: number | null,
+    /** local hour scheduled scans can start from, set with scan_quiet_end */
+    scan_quiet_start: number | null,
+    /** local hour scheduled scans stop starting at, wraps past midnight if before scan_quiet_start */
+    scan_quiet_end: number | null,
+    /** speed of startup and scheduled scans */
+    scheduled_scan_speed: ScanSpeed,
+    /** addons folder is of a dedicated server (srcds) */
+    server_mode: boolean,
+    /** collection for host_workshop_collection */
+    server_collection_id: number | null,
+    /** fastdl url for sv_downloadurl */
+    server_download_url: string | null,
+    /** addons_folder and server_mode are of the active installation */
+    installations: Installation[],
+    active_installation: number | null
+}
+
+/** A game or dedicated server install, each has its own database */
+export interface Installation {
+    id: number,
+    name: string,
+    addons_folder: string,
+    server_mode: boolean
+}
+
+export interface InstallationList {
+    installations: Installation[],
+    active: number | null
+}
+
+export interface ServerConfigOutput {
+    /** where config was written to */
+    path: string,
+    contents: string,
+    /** workshop ids of enabled addons */
+    workshop_ids: number[]
 }
 
 export type ItemResult = ItemResult_Ok | ItemResult_Error
@@ -51,6 +92,38 @@ export interface ProgressPayload {
     total: number
 }
 
+export interface ImportSummary {
+    path: string,
+    /** app version export was made with */
+    version: string,
+    addon_files: number,
+    addon_files_size: number,
+    /** addon files that already exist, and will be skipped */
+    existing_addon_files: number,
+    /** export's addons folder, if different. local folder is kept */
+    other_addons_folder: string | null
+}
+
+export type FastDlSource = { type: "profile", id: number } | { type: "addons", ids: string[] }
+
+export interface FastDlSummary {
+    /** files in manifest */
+    files: number,
+    /** files compressed this run */
+    written: number,
+    /** files unchanged since last export to same folder */
+    unchanged: number,
+    size: number,
+    compressed_size: number,
+    /** paths other addons already provided, left out */
+    conflicts: string[],
+    /** addons or files that could not be read, left out, as "filename: error" */
+    skipped: string[],
+    /** files of previous export no longer exported, deleted */
+    removed: number,
+    cancelled: boolean
+}
+
 export interface UpdateData {
     version?: string,
     updating?: boolean
@@ -0,0 +1,35 @@
+export interface ProfileItems {
+    /** managed addon ids (file hash) */
+    ids: string[],
+    workshop_ids: number[]
+}
+
+export interface Profile {
+    id: number,
+    name: string,
+    /** unix timestamp */
+    created_at: number,
+    /** unix timestamp */
+    updated_at: number,
+    items: ProfileItems
+}
+
+export interface CollectionImport {
+    /** id of created profile */
+    profile_id: number,
+    name: string,
+    /** workshop ids of items in the workshop folder */
+    subscribed: number[],
+    /** workshop ids of items with a managed copy */
+    managed: number[],
+    /** workshop ids of items not subscribed to or managed */
+    missing: number[]
+}
+
+export interface ProfileCopy {
+    /** id of profile in the other installation */
+    profile_id: number,
+    name: string,
+    /** addon ids the other installation does not have */
+    missing: string[]
+}
@@ -1,10 +1,18 @@
-export const enum ScanSpeed {
+export const enum ScanSpeedPreset {
     Maximum = "maximum",
     Normal = "normal",
     Background = "background"
-} 
+}
+export interface ScanSpeedCustom {
+    custom: {
+        threads: number,
+        /** limit of how fast files are hashed, null for no limit */
+        max_mb_per_sec: number | null
+    }
+}
+export type ScanSpeed = ScanSpeedPreset | ScanSpeedCustom
 
-export type ScanStateEvent = ScanStateEvent_Started | ScanStateEvent_Aborted | ScanStateEvent_Complete
+export type ScanStateEvent = ScanStateEvent_Started | ScanStateEvent_Aborted | ScanStateEvent_Paused | ScanStateEvent_Resumed | ScanStateEvent_Complete
 export interface ScanStateEvent_Started {
     state: "started",
     speed: ScanSpeed
@@ -13,19 +21,60 @@ export interface ScanStateEvent_Aborted {
     state: "aborted",
     reason?: string
 }
+/** workers are parked, scan keeps its queue */
+export interface ScanStateEvent_Paused {
+    state: "paused"
+}
+export interface ScanStateEvent_Resumed {
+    state: "resumed"
+}
 export interface ScanStateEvent_Complete {
     state: "complete",
     time: number // seconds
     total: number,
     added: number,
     updated: number,
+    /** files unchanged since last scan */
+    skipped: number,
     failed: number
 }
 
+export interface ScanRecord {
+    id: number,
+    /** unix seconds */
+    started_at: number,
+    ended_at: number | null,
+    speed: string,
+    status: "running" | "complete" | "aborted",
+    total: number,
+    added: number,
+    updated: number,
+    skipped: number,
+    failed: number,
+    aborted_reason: string | null
+}
+
+/** A file that could not be scanned or stored */
+export interface ScanError {
+    scan_id: number,
+    filename: string,
+    /** set if file is in the workshop folder */
+    workshop_id: number | null,
+    error: string
+}
+
+export interface WatchedEntry {
+    filename: string,
+    /** is entry from the workshop folder */
+    workshop: boolean,
+    change: "added" | "updated" | "removed"
+}
+
 export type ScanResultType = "updated" | "renamed" | "added" | "no_action"
 
 export const enum ScanState {
     Inactive,
     Running,
+    Paused,
     Cancelling
 }
\ No newline at end of file